@@ -1,5 +1,6 @@
-use crate::common::error::Error;
-use crate::server::storage::IncidentItem;
+use crate::common::{error::Error, utils::format_duration};
+use crate::server::storage::{IncidentItem, IncidentStatus};
+
 use super::utils::{api_get, format_timestamp};
 
 fn get_error_message(error_message: &Option<String>) -> String {
@@ -10,6 +11,14 @@ fn get_error_message(error_message: &Option<String>) -> String {
     }
 }
 
+fn get_status_label(status: IncidentStatus) -> &'static str {
+
+    match status {
+        IncidentStatus::Open => "open",
+        IncidentStatus::Resolved => "resolved"
+    }
+}
+
 pub async fn list_incidents(base_url: &str, token: &str) -> Result<(), Error> {
 
     let raw_incidents: Vec<IncidentItem> = api_get(base_url, token, "api/v1/incidents").await?;
@@ -40,13 +49,15 @@ pub async fn list_incidents(base_url: &str, token: &str) -> Result<(), Error> {
     }
 
     println!();
-    println!("| ID   | {: <h_max$} | {: <v_max$} | {: <e_max$} |", "Timestamp", "Message", "Details", h_max=timestamp_length, v_max=message_length, e_max=error_length);
-    println!("|------|-{:-<h_max$}-|-{:-<v_max$}-|-{:-<e_max$}-|", "", "", "", h_max=timestamp_length, v_max=message_length, e_max=error_length);
+    println!("| ID   | {: <h_max$} | Status   | Duration   | {: <v_max$} | {: <e_max$} |", "Timestamp", "Message", "Details", h_max=timestamp_length, v_max=message_length, e_max=error_length);
+    println!("|------|-{:-<h_max$}-|----------|------------|-{:-<v_max$}-|-{:-<e_max$}-|", "", "", "", h_max=timestamp_length, v_max=message_length, e_max=error_length);
 
     for incident in incidents.iter() {
     
         let error_message = get_error_message(&incident.error_message);
-        println!("| {: <4} | {: <h_max$} | {: <v_max$} | {: <e_max$} |", incident.id, incident.timestamp, incident.message, error_message, h_max=timestamp_length, v_max=message_length, e_max=error_length);
+        let status = get_status_label(incident.status);
+        let duration = format_duration(incident.duration_secs);
+        println!("| {: <4} | {: <h_max$} | {: <8} | {: <10} | {: <v_max$} | {: <e_max$} |", incident.id, incident.timestamp, status, duration, incident.message, error_message, h_max=timestamp_length, v_max=message_length, e_max=error_length);
     }
     println!();
 
@@ -63,9 +74,32 @@ pub async fn inspect_incident(base_url: &str, token: &str, incident_id: &str) ->
     println!();
     println!("Incident ID\t{}", incident.id);
     println!("Timestamp\t{}", incident.timestamp);
+    println!("Status\t\t{}", get_status_label(incident.status));
+    if let Some(resolved_at) = &incident.resolved_at {
+        println!("Resolved at\t{}", format_timestamp(resolved_at));
+    }
+    println!("Duration\t{}", format_duration(incident.duration_secs));
     println!("Message\t\t{}", incident.message);
     println!("Details\t\t{}", get_error_message(&incident.error_message));
+    if let Some(error_details) = &incident.error_details {
+        println!("Cause\t\t{}", error_details);
+    }
     println!();
 
+    if !incident.tests.is_empty() {
+
+        println!("Tests");
+        for outcome in incident.tests.iter() {
+
+            let result = format!("{:?}", outcome.result).to_uppercase();
+            let cause = outcome.reason.as_ref().map(|reason| reason.to_string()).or_else(|| outcome.error.clone());
+            match cause {
+                Some(cause) => println!(" - {: <8}{} ({}ms, {})", result, outcome.test, outcome.duration_ms, cause),
+                None => println!(" - {: <8}{} ({}ms)", result, outcome.test, outcome.duration_ms)
+            }
+        }
+        println!();
+    }
+
     Ok(())
 }
@@ -1,7 +1,7 @@
 use std::vec;
 
 use crate::common::error::Error;
-use crate::server::config::{ConfigInput, RegionConfigInput, GroupConfigInput};
+use crate::server::config::{ConfigInput, RegionConfigInput, GroupConfigInput, TestConfigInput};
 
 pub fn init_config() -> Result<(), Error> {
 
@@ -15,6 +15,7 @@ pub fn init_config() -> Result<(), Error> {
 
     let mut config = ConfigInput {
         alerters: Some(vec![]),
+        alert_routes: None,
         regions: vec![]
     };
 
@@ -29,16 +30,21 @@ pub fn init_config() -> Result<(), Error> {
             groups: vec![GroupConfigInput {
                 name: "default".to_string(),
                 tests: vec![
-                    "ping 1.1.1.1".to_string(),
-                    "dns example.org".to_string(),
-                    "http example.org".to_string()
+                    TestConfigInput::Command("ping 1.1.1.1".to_string()),
+                    TestConfigInput::Command("dns example.org".to_string()),
+                    TestConfigInput::Command("http example.org".to_string())
                 ],
-                fail_threshold: Some(4)
+                fail_threshold: Some(4),
+                thresholds: None,
+                severity: None
             }],
             name: region_name,
             send_interval: Some("5s".to_string()),
             miss_threshold: Some(3),
-            kuma_url: None
+            kuma_url: None,
+            concurrency: None,
+            test_timeout: None,
+            thresholds: None
         })
     }
 
@@ -2,4 +2,6 @@ pub mod incident;
 pub mod status;
 pub mod utils;
 pub mod init;
-pub mod alerting;
\ No newline at end of file
+pub mod alerting;
+pub mod test;
+pub mod probe;
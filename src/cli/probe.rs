@@ -0,0 +1,62 @@
+use std::time::Duration;
+
+use serde::Deserialize;
+use serde_json::json;
+use tokio::time::{sleep, Instant};
+
+use crate::common::error::Error;
+use crate::server::storage::{ProbeItem, ProbeStatus};
+use crate::relay::display::display_outcome;
+use super::utils::{api_get, api_post_json};
+
+const POLL_INTERVAL_MS: u64 = 1000;
+
+#[derive(Deserialize)]
+struct ProbeQueued {
+    id: u32
+}
+
+/// Queue a test on the relay of a region and wait for its result, the relay only
+/// picks up the test on its next update (up to the send interval of the region)
+pub async fn run_probe(base_url: &str, token: &str, region: &str, test: &str, timeout_ms: u64) -> Result<(), Error> {
+
+    let probe_body = json!({
+        "region": region,
+        "test": test
+    });
+    let queued_probe: ProbeQueued = api_post_json(base_url, token, "api/v1/probes", &probe_body).await?;
+
+    println!("Probe {} queued on region {}, waiting for the relay...", queued_probe.id, region);
+
+    let probe_route = format!("api/v1/probes/{}", queued_probe.id);
+    let wait_deadline = Instant::now() + Duration::from_millis(timeout_ms);
+
+    loop {
+
+        let probe: ProbeItem = api_get(base_url, token, &probe_route).await?;
+
+        match (probe.status, probe.result) {
+            (ProbeStatus::Done, Some(result)) => {
+
+                let mut metrics: Vec<(String, f32)> = result.metrics.into_iter().collect();
+                metrics.sort_by(|first, second| first.0.cmp(&second.0));
+
+                println!();
+                display_outcome(test, result.result, result.duration_ms, result.reason.as_ref(), result.error.as_deref(), &metrics);
+                println!();
+
+                return Ok(());
+            },
+            (ProbeStatus::Expired, _) => {
+                return Err(Error::basic(format!("Probe {} expired, the relay of region {} did not pick it up", probe.id, region)));
+            },
+            _ => {}
+        }
+
+        if Instant::now() >= wait_deadline {
+            return Err(Error::basic(format!("No result for probe {} after {}ms (status {:?})", probe.id, timeout_ms, probe.status)));
+        }
+
+        sleep(Duration::from_millis(POLL_INTERVAL_MS)).await;
+    }
+}
@@ -2,6 +2,7 @@ use chrono::prelude::*;
 use ansi_term::{Colour, Style};
 
 use crate::common::error::Error;
+use crate::relay::model::ResultCategory;
 use crate::server::storage::RegionSummary;
 use super::utils::api_get;
 
@@ -38,7 +39,7 @@ pub async fn display_status(base_url: &str, token: &str) -> Result<(), Error> {
                 continue;
             }
 
-            let group_name = match group.name.split('.').last() {
+            let group_name = match group.name.split('.').next_back() {
                 Some(name) => format!("Group {}", name),
                 None => group.name.to_string()
             };
@@ -53,7 +54,20 @@ pub async fn display_status(base_url: &str, token: &str) -> Result<(), Error> {
             };
 
             println!(" - {: <n_max$}{: <s_max$}", group_name, group_status, n_max=24, s_max=30);
-            
+
+            // Only tests that are not working are displayed, to keep the status short
+            for outcome in group.tests.iter().filter(|outcome| outcome.result != ResultCategory::Success) {
+
+                let test_status = match outcome.result {
+                    ResultCategory::Warning => Colour::Yellow.paint("warn"),
+                    _ => Colour::Red.paint("fail")
+                };
+                let cause = outcome.reason.as_ref().map(|reason| reason.to_string()).or_else(|| outcome.error.clone());
+                match cause {
+                    Some(cause) => println!("     {} {} ({})", test_status, outcome.test, cause),
+                    None => println!("     {} {}", test_status, outcome.test)
+                }
+            }
         }
         println!();
     }
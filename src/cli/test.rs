@@ -0,0 +1,55 @@
+use std::time::Duration;
+
+use tokio::time::{timeout, Instant};
+
+use crate::common::error::Error;
+use crate::relay::display::display_outcome;
+use crate::relay::model::{FailureReason, ResultCategory};
+use crate::relay::test::runner::TestRunner;
+use crate::server::config::ThresholdConfig;
+
+/// Execute a single test locally (nothing is sent to the server), the test is
+/// considered as failed after the given timeout. A failed test is returned as an
+/// error, the command can be used in scripts (warnings are not failures).
+pub async fn run_test(test: &str, timeout_ms: u64) -> Result<(), Error> {
+
+    let runner = TestRunner::new();
+    runner.parse_test(test)?;
+
+    let test_start = Instant::now();
+    let test_result = timeout(Duration::from_millis(timeout_ms), runner.execute_test(test, &ThresholdConfig::default())).await;
+    let duration_ms = test_start.elapsed().as_millis() as u64;
+
+    println!();
+    let category = match test_result {
+        Ok(Ok(result)) => {
+
+            let mut metrics: Vec<(String, f32)> = result.metrics.unwrap_or_default().into_iter().collect();
+            metrics.sort_by(|first, second| first.0.cmp(&second.0));
+
+            display_outcome(test, result.result, duration_ms, result.reason.as_ref(), result.message.as_deref(), &metrics);
+            result.result
+        },
+        Ok(Err(err)) => {
+
+            let message = match &err.details {
+                Some(details) => format!("{}: {}", err.message, details),
+                None => err.message.clone()
+            };
+            display_outcome(test, ResultCategory::Fail, duration_ms, None, Some(&message), &[]);
+            ResultCategory::Fail
+        },
+        Err(_elapsed) => {
+            let message = format!("timed out after {}ms", timeout_ms);
+            display_outcome(test, ResultCategory::Fail, duration_ms, Some(&FailureReason::Timeout), Some(&message), &[]);
+            ResultCategory::Fail
+        }
+    };
+    println!();
+
+    if category == ResultCategory::Fail {
+        return Err(Error::basic(format!("Test '{}' failed", test)));
+    }
+
+    Ok(())
+}
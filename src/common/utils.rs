@@ -0,0 +1,12 @@
+/// Human-friendly duration, such as "2h 05m" or "45s"
+pub fn format_duration(duration_secs: i64) -> String {
+
+    let duration_secs = duration_secs.max(0);
+    let (hours, minutes, seconds) = (duration_secs / 3600, (duration_secs % 3600) / 60, duration_secs % 60);
+
+    match (hours, minutes) {
+        (0, 0) => format!("{}s", seconds),
+        (0, _) => format!("{}m {:02}s", minutes, seconds),
+        _ => format!("{}h {:02}m", hours, minutes)
+    }
+}
@@ -0,0 +1,4 @@
+pub mod common;
+pub mod relay;
+pub mod server;
+pub mod cli;
@@ -1,16 +1,13 @@
-mod common;
-mod relay;
-mod server;
-mod cli;
-
 use std::env;
+use std::path::PathBuf;
 use std::process;
 
-use clap::{Arg, Command};
-use cli::alerting;
+use clap::{Arg, ArgAction, Command};
 
-use crate::cli::{incident, status, init};
-use crate::common::error::Error;
+use watchdog_rs::{relay, server};
+use watchdog_rs::cli::{alerting, incident, status, init, probe, test};
+use watchdog_rs::common::error::Error;
+use watchdog_rs::server::config::parse_to_milliseconds;
 
 const VERSION: &str = env!("CARGO_PKG_VERSION");
 
@@ -44,13 +41,16 @@ async fn main() {
                         process::exit(1);
                     });
 
+                    let data_dir = server_matches.get_one::<String>("data-dir").map(PathBuf::from);
+
                     let server_conf = server::config::ServerConf {
                         config_path: config_path.to_string(),
                         port,
                         address,
                         token,
                         telegram_token: env::var("TELEGRAM_TOKEN").ok(),
-                        telegram_chat: env::var("TELEGRAM_CHAT").ok()
+                        telegram_chat: env::var("TELEGRAM_CHAT").ok(),
+                        data_dir
                     };
 
                     let server_result = server::service::launch(server_conf).await;
@@ -75,12 +75,46 @@ async fn main() {
         },
         Some(("relay", relay_matches)) => {
 
-            let (base_url, token) = extract_watchdog_env_or_fail();
+            let dry_run = relay_matches.get_flag("dry-run");
+            let config_path = relay_matches.get_one::<String>("config").cloned();
+
+            if config_path.is_some() && !dry_run {
+                eprintln!("A local configuration file can only be used with the --dry-run option");
+                process::exit(1);
+            }
+
+            // Dry runs with a local configuration do not contact the server
+            let (base_url, token) = match config_path {
+                Some(_) => (env::var("WATCHDOG_ADDR").unwrap_or_default(), env::var("WATCHDOG_TOKEN").unwrap_or_default()),
+                None => extract_watchdog_env_or_fail()
+            };
 
             match relay_matches.get_one::<String>("region") {
                 Some(region_name) => {
 
-                    let relay_result = relay::service::launch(base_url, token, region_name.to_string()).await;
+                    let data_dir = relay_matches.get_one::<String>("data-dir")
+                        .map(PathBuf::from)
+                        .unwrap_or_else(|| PathBuf::from(relay::config::DEFAULT_DATA_DIR));
+
+                    let buffer_size = relay_matches.get_one::<String>("buffer-size")
+                        .map(|size| size.parse::<usize>().unwrap_or_else(|_| {
+                            eprintln!("The buffer size must be a number of updates (0 disables the buffer), found '{}'", size);
+                            process::exit(1);
+                        }))
+                        .unwrap_or(relay::config::DEFAULT_BUFFER_SIZE);
+
+                    let relay_conf = relay::config::RelayConf {
+                        base_url,
+                        token,
+                        region_name: region_name.to_string(),
+                        data_dir,
+                        buffer_size,
+                        once: relay_matches.get_flag("once"),
+                        dry_run,
+                        config_path
+                    };
+
+                    let relay_result = relay::service::launch(relay_conf).await;
 
                     if let Err(relay_err) = relay_result {
                         eprintln!("The watchdog relay process failed, see details below");
@@ -98,6 +132,39 @@ async fn main() {
                 }
             };
 
+        },
+        Some(("test", test_matches)) => {
+
+            let test_spec = test_matches.get_one::<String>("test").expect("Expecting a test to run");
+
+            let timeout_ms = test_matches.get_one::<String>("timeout")
+                .map(|timeout| parse_to_milliseconds(timeout).unwrap_or_else(|err| {
+                    eprintln!("The test timeout has an {}", err);
+                    process::exit(1);
+                }))
+                .unwrap_or(10000);
+
+            let cli_result = test::run_test(test_spec, timeout_ms).await;
+            handle_cli_failure(cli_result);
+
+        },
+        Some(("probe", probe_matches)) => {
+
+            let (base_url, token) = extract_watchdog_env_or_fail();
+
+            let region = probe_matches.get_one::<String>("region").expect("Expecting a probe region");
+            let test_spec = probe_matches.get_one::<String>("test").expect("Expecting a test to run");
+
+            let timeout_ms = probe_matches.get_one::<String>("timeout")
+                .map(|timeout| parse_to_milliseconds(timeout).unwrap_or_else(|err| {
+                    eprintln!("The probe timeout has an {}", err);
+                    process::exit(1);
+                }))
+                .unwrap_or(60000);
+
+            let cli_result = probe::run_probe(&base_url, &token, region, test_spec, timeout_ms).await;
+            handle_cli_failure(cli_result);
+
         },
         Some(("status", _)) =>  {
 
@@ -211,6 +278,9 @@ fn build_args() -> clap::Command {
                 .long("address")
                 .help("Listen address for the server")
                 .default_value(server::service::DEFAULT_ADDRESS))
+            .arg(Arg::new("data-dir")
+                .long("data-dir")
+                .help("Directory used to keep incidents and history between restarts (in memory otherwise)"))
         )
         .subcommand(Command::new("relay")
             .about("Launch relay daemon")
@@ -220,6 +290,62 @@ fn build_args() -> clap::Command {
                 .help("Network region covered by relay")
                 .required(true)
             )
+            .arg(Arg::new("data-dir")
+                .long("data-dir")
+                .help("Directory used to keep relay data (such as buffered results), relative to the working directory unless absolute: use an absolute path when started by a service manager such as systemd")
+                .default_value(relay::config::DEFAULT_DATA_DIR)
+            )
+            .arg(Arg::new("buffer-size")
+                .long("buffer-size")
+                .help(format!("Maximum number of region updates buffered while the server is unreachable [default: {}]", relay::config::DEFAULT_BUFFER_SIZE))
+            )
+            .arg(Arg::new("once")
+                .long("once")
+                .help("Run the region tests once and exit")
+                .action(ArgAction::SetTrue)
+            )
+            .arg(Arg::new("dry-run")
+                .long("dry-run")
+                .help("Display the test results without sending anything to the server")
+                .action(ArgAction::SetTrue)
+            )
+            .arg(Arg::new("config")
+                .short('c')
+                .long("config")
+                .help("Local YAML config used instead of the server config (dry runs only)")
+            )
+        )
+        .subcommand(Command::new("test")
+            .about("Run a test locally and display the result")
+            .arg(Arg::new("test")
+                .help("Test to run, such as \"http example.org status=200\"")
+                .required(true)
+            )
+            .arg(Arg::new("timeout")
+                .short('t')
+                .long("timeout")
+                .help("Time after which the test is considered as failed")
+                .default_value("10s")
+            )
+        )
+        .subcommand(Command::new("probe")
+            .about("Run a test on the relay of a region and display the result")
+            .arg(Arg::new("region")
+                .short('r')
+                .long("region")
+                .help("Region in which the test is executed")
+                .required(true)
+            )
+            .arg(Arg::new("test")
+                .help("Test to run, such as \"ping 10.1.2.3\"")
+                .required(true)
+            )
+            .arg(Arg::new("timeout")
+                .short('t')
+                .long("timeout")
+                .help("Time to wait for the result of the relay")
+                .default_value("60s")
+            )
         )
         .subcommand(Command::new("status")
             .about("Status overview for all regions")
@@ -1,15 +1,41 @@
 use reqwest::Client;
 
-use crate::relay::model::GroupResultInput;
+use crate::relay::model::{GroupResultInput, ProbeRequest, ProbeResultInput, RegionBackfillInput, RegionUpdateOutput};
 use crate::server::config::RegionConfig;
 use crate::common::error::Error;
 
+/// Response of the server to a region update
+pub struct RegionUpdate {
+
+    /// New configuration version, only set when the version changed since the last update
+    pub version: Option<String>,
+
+    /// On-demand probes queued for the region
+    pub probes: Vec<ProbeRequest>
+
+}
+
+/// Failure of a buffered updates replay
+pub enum BackfillError {
+
+    /// The server could not be reached or failed (5xx), the updates can be replayed later
+    Unavailable(Error),
+
+    /// The server refused the updates (4xx, such as a region removed from the server
+    /// configuration), replaying the same updates would always fail
+    Rejected(Error)
+
+}
+
+#[derive(Clone)]
 pub struct ServerApi {
 
     client: Client,
     authorization_header: String,
     config_route: String,
-    update_route: String
+    update_route: String,
+    backfill_route: String,
+    probe_route: String
 
 }
 
@@ -22,12 +48,16 @@ impl ServerApi {
         
         let config_route = format!("{}/api/v1/relay/{}", base_url, region_name);
         let update_route = format!("{}/api/v1/relay/{}", base_url, region_name);
+        let backfill_route = format!("{}/api/v1/relay/{}/backfill", base_url, region_name);
+        let probe_route = format!("{}/api/v1/relay/{}/probes", base_url, region_name);
 
         ServerApi {
             client,
             authorization_header,
             config_route,
-            update_route
+            update_route,
+            backfill_route,
+            probe_route
         }
     }
 
@@ -54,7 +84,7 @@ impl ServerApi {
         serde_json::from_str::<RegionConfig>(&body).map_err(|err| Error::new("Failed to decode JSON region config", err))
     }
 
-    pub async fn update_region_state(&self, group_results: &Vec<GroupResultInput>, last_update: &str) -> Result<Option<String>, Error> {
+    pub async fn update_region_state(&self, group_results: &Vec<GroupResultInput>, last_update: &str) -> Result<RegionUpdate, Error> {
 
         let json_state = serde_json::to_string(&group_results)
             .map_err(|err| Error::new("Could not parse region state to JSON", err))?;
@@ -68,16 +98,78 @@ impl ServerApi {
             .await
             .map_err(|err| Error::new("Could not update region state", err))?;
 
+        let mut version: Option<String> = None;
         if let Some(header_value) = response.headers().get("X-Watchdog-Update") {
 
             let watchdog_update = header_value.to_str().unwrap_or("unknown");
 
             if watchdog_update != last_update {
-                return Ok(Some(watchdog_update.to_string()))
+                version = Some(watchdog_update.to_string());
             }
         }
 
-        Ok(None)
+        // The update has been delivered at this point, a body that can not be decoded
+        // (such as an older server) only means that no probe is pending
+        let probes = match response.json::<RegionUpdateOutput>().await {
+            Ok(update_output) => update_output.probes,
+            Err(_) => vec![]
+        };
+
+        Ok(RegionUpdate { version, probes })
+    }
+
+    pub async fn send_probe_result(&self, probe_id: u32, probe_result: &ProbeResultInput) -> Result<(), Error> {
+
+        let json_result = serde_json::to_string(probe_result)
+            .map_err(|err| Error::new("Could not parse probe result to JSON", err))?;
+
+        let response = self.client.post(format!("{}/{}", self.probe_route, probe_id))
+            .header("Content-Type", "application/json")
+            .header("Authorization", &self.authorization_header)
+            .header("Accept", "application/json")
+            .body(json_result)
+            .send()
+            .await
+            .map_err(|err| Error::new("Could not send probe result", err))?;
+
+        if response.status() != 200 {
+            return Err(
+                Error::basic(format!("Expected status code 200 for probe result, found {}", response.status()))
+            );
+        }
+
+        Ok(())
+    }
+
+    /// Send region updates that could not be delivered on time, the server keeps
+    /// them in the history without considering them as heartbeats
+    pub async fn backfill_region_state(&self, updates: &[RegionBackfillInput]) -> Result<(), BackfillError> {
+
+        let json_updates = serde_json::to_string(&updates)
+            .map_err(|err| BackfillError::Rejected(Error::new("Could not parse region updates to JSON", err)))?;
+
+        let response = self.client.post(&self.backfill_route)
+            .header("Content-Type", "application/json")
+            .header("Authorization", &self.authorization_header)
+            .header("Accept", "application/json")
+            .body(json_updates)
+            .send()
+            .await
+            .map_err(|err| BackfillError::Unavailable(Error::new("Could not replay buffered region updates", err)))?;
+
+        let status = response.status();
+        if status.is_client_error() {
+            return Err(BackfillError::Rejected(
+                Error::basic(format!("Server rejected buffered updates with status code {}", status))
+            ));
+        }
+        if status != 200 {
+            return Err(BackfillError::Unavailable(
+                Error::basic(format!("Expected status code 200 for buffered updates, found {}", status))
+            ));
+        }
+
+        Ok(())
     }
 
     pub async fn trigger_kuma_update(&self, kuma_url: &str, total_groups: usize, unstable_groups: usize, last_ping: Option<f32>) -> Result<(), Error> {
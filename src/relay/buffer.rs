@@ -0,0 +1,277 @@
+use std::collections::VecDeque;
+use std::path::{Path, PathBuf};
+
+use tokio::fs;
+use tokio::io::AsyncWriteExt;
+
+use crate::common::error::Error;
+use crate::relay::model::RegionBackfillInput;
+
+/**
+ * Bounded on-disk buffer of the region updates that could not be sent to the
+ * server. The updates are kept in memory and appended to a JSON lines file (one
+ * update per line), the file is only rewritten once the updates have been replayed
+ * or when enough dropped updates are left in the file (at most twice the capacity).
+ */
+pub struct ResultBuffer {
+    path: PathBuf,
+    capacity: usize,
+    updates: VecDeque<RegionBackfillInput>,
+    file_lines: usize
+}
+
+impl ResultBuffer {
+
+    pub async fn open(path: &Path, capacity: usize) -> Result<ResultBuffer, Error> {
+
+        let content = match fs::read_to_string(path).await {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(Error::new(format!("Could not read buffer {}", path.display()), err))
+        };
+
+        // Lines that can not be decoded (such as a partial write) are skipped
+        let line_count = content.lines().count();
+        let mut updates: VecDeque<RegionBackfillInput> = content.lines()
+            .filter_map(|line| serde_json::from_str::<RegionBackfillInput>(line).ok())
+            .collect();
+
+        let dropped_count = updates.len().saturating_sub(capacity);
+        updates.drain(..dropped_count);
+
+        let mut buffer = ResultBuffer {
+            path: path.to_path_buf(),
+            capacity,
+            updates,
+            file_lines: line_count
+        };
+
+        // A partial line left by a crash would be joined with the next update, the
+        // file is rewritten with the decoded updates only
+        let is_truncated = !content.is_empty() && !content.ends_with('\n');
+        if is_truncated || buffer.updates.len() != line_count {
+            buffer.rewrite().await?;
+        }
+
+        Ok(buffer)
+    }
+
+    pub fn len(&self) -> usize {
+        self.updates.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.updates.is_empty()
+    }
+
+    /// Buffered updates, from the oldest to the most recent one
+    pub fn updates(&self) -> Vec<RegionBackfillInput> {
+        self.updates.iter().cloned().collect()
+    }
+
+    pub async fn push(&mut self, update: &RegionBackfillInput) -> Result<(), Error> {
+
+        if self.capacity == 0 {
+            return Ok(());
+        }
+
+        self.updates.push_back(update.clone());
+        if self.updates.len() > self.capacity {
+            self.updates.pop_front();
+        }
+
+        // The dropped updates stay in the file until enough of them are left, the
+        // file is then rewritten once for a full capacity of pushed updates
+        if self.file_lines >= self.capacity * 2 {
+            return self.rewrite().await;
+        }
+
+        let line = encode_line(update)?;
+
+        // The directory is only created on the first buffered update
+        if let Some(parent_dir) = self.path.parent() {
+            fs::create_dir_all(parent_dir).await
+                .map_err(|err| Error::new(format!("Could not create buffer directory {}", parent_dir.display()), err))?;
+        }
+
+        let mut file = fs::OpenOptions::new().create(true).append(true).open(&self.path).await
+            .map_err(|err| Error::new(format!("Could not open buffer {}", self.path.display()), err))?;
+        // Tokio files complete the writes in the background, the update must be
+        // written before the buffer is read again
+        file.write_all(line.as_bytes()).await
+            .map_err(|err| Error::new(format!("Could not write buffer {}", self.path.display()), err))?;
+        file.flush().await
+            .map_err(|err| Error::new(format!("Could not write buffer {}", self.path.display()), err))?;
+
+        self.file_lines += 1;
+
+        Ok(())
+    }
+
+    /// Replace the buffered updates (an empty list removes the buffer file)
+    pub async fn replace(&mut self, updates: &[RegionBackfillInput]) -> Result<(), Error> {
+
+        self.updates = updates.iter().cloned().collect();
+        self.rewrite().await
+    }
+
+    /// Write the buffered updates only, the previous file content is dropped
+    async fn rewrite(&mut self) -> Result<(), Error> {
+
+        if self.updates.is_empty() {
+
+            match fs::remove_file(&self.path).await {
+                Ok(_) => {},
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => {},
+                Err(err) => return Err(Error::new(format!("Could not remove buffer {}", self.path.display()), err))
+            }
+
+            self.file_lines = 0;
+            return Ok(());
+        }
+
+        let mut content = String::new();
+        for update in self.updates.iter() {
+            content.push_str(&encode_line(update)?);
+        }
+
+        // The buffer is written in a temporary file first, a crash while writing
+        // must not lose the updates already buffered
+        let temporary_path = self.path.with_extension("tmp");
+        fs::write(&temporary_path, content).await
+            .map_err(|err| Error::new(format!("Could not write buffer {}", temporary_path.display()), err))?;
+        fs::rename(&temporary_path, &self.path).await
+            .map_err(|err| Error::new(format!("Could not replace buffer {}", self.path.display()), err))?;
+
+        self.file_lines = self.updates.len();
+
+        Ok(())
+    }
+
+}
+
+fn encode_line(update: &RegionBackfillInput) -> Result<String, Error> {
+
+    let mut line = serde_json::to_string(update)
+        .map_err(|err| Error::new("Could not encode buffered update", err))?;
+    line.push('\n');
+
+    Ok(line)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::relay::model::GroupResultInput;
+
+    use super::*;
+
+    fn build_update(timestamp: &str) -> RegionBackfillInput {
+
+        RegionBackfillInput {
+            timestamp: timestamp.to_string(),
+            groups: vec![GroupResultInput {
+                name: "default".to_string(),
+                working: false,
+                has_warnings: false,
+                error_message: Some("test 'ping 10.0.0.1' failed (100% packet loss)".to_string()),
+                error_detail: None,
+                tests: vec![],
+                metrics: vec![]
+            }]
+        }
+    }
+
+    fn build_buffer_path(name: &str) -> PathBuf {
+
+        let path = std::env::temp_dir().join(format!("watchdog-{}-{}", std::process::id(), name)).join("buffer.jsonl");
+        let _ = std::fs::remove_file(&path);
+        path
+    }
+
+    #[tokio::test]
+    async fn should_keep_updates_between_restarts() {
+
+        let path = build_buffer_path("restart");
+
+        let mut buffer = ResultBuffer::open(&path, 10).await.unwrap();
+        buffer.push(&build_update("2023-05-01T10:00:00Z")).await.unwrap();
+        buffer.push(&build_update("2023-05-01T10:00:05Z")).await.unwrap();
+
+        let buffer = ResultBuffer::open(&path, 10).await.unwrap();
+        let updates = buffer.updates();
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(updates[0].timestamp, "2023-05-01T10:00:00Z");
+        assert_eq!(updates[1].groups[0].name, "default");
+    }
+
+    #[tokio::test]
+    async fn should_drop_oldest_updates() {
+
+        let path = build_buffer_path("bounded");
+
+        let mut buffer = ResultBuffer::open(&path, 2).await.unwrap();
+        buffer.push(&build_update("2023-05-01T10:00:00Z")).await.unwrap();
+        buffer.push(&build_update("2023-05-01T10:00:05Z")).await.unwrap();
+        buffer.push(&build_update("2023-05-01T10:00:10Z")).await.unwrap();
+
+        let timestamps: Vec<String> = buffer.updates().into_iter().map(|update| update.timestamp).collect();
+
+        assert_eq!(buffer.len(), 2);
+        assert_eq!(timestamps, vec!["2023-05-01T10:00:05Z", "2023-05-01T10:00:10Z"]);
+    }
+
+    #[tokio::test]
+    async fn should_bound_buffer_file() {
+
+        let path = build_buffer_path("compaction");
+
+        let mut buffer = ResultBuffer::open(&path, 2).await.unwrap();
+        for second in 0..10 {
+            buffer.push(&build_update(&format!("2023-05-01T10:00:{:02}Z", second))).await.unwrap();
+        }
+
+        let line_count = std::fs::read_to_string(&path).unwrap().lines().count();
+        assert!(line_count <= 4);
+
+        let buffer = ResultBuffer::open(&path, 2).await.unwrap();
+        let timestamps: Vec<String> = buffer.updates().into_iter().map(|update| update.timestamp).collect();
+        assert_eq!(timestamps, vec!["2023-05-01T10:00:08Z", "2023-05-01T10:00:09Z"]);
+    }
+
+    #[tokio::test]
+    async fn should_skip_partial_updates() {
+
+        let path = build_buffer_path("partial");
+
+        let mut buffer = ResultBuffer::open(&path, 10).await.unwrap();
+        buffer.push(&build_update("2023-05-01T10:00:00Z")).await.unwrap();
+
+        // Crash while appending an update
+        let mut file = std::fs::OpenOptions::new().append(true).open(&path).unwrap();
+        std::io::Write::write_all(&mut file, b"{\"timestamp\":\"2023-05-").unwrap();
+
+        let mut buffer = ResultBuffer::open(&path, 10).await.unwrap();
+        buffer.push(&build_update("2023-05-01T10:00:10Z")).await.unwrap();
+
+        let buffer = ResultBuffer::open(&path, 10).await.unwrap();
+        let timestamps: Vec<String> = buffer.updates().into_iter().map(|update| update.timestamp).collect();
+        assert_eq!(timestamps, vec!["2023-05-01T10:00:00Z", "2023-05-01T10:00:10Z"]);
+    }
+
+    #[tokio::test]
+    async fn should_clear_replayed_updates() {
+
+        let path = build_buffer_path("clear");
+
+        let mut buffer = ResultBuffer::open(&path, 10).await.unwrap();
+        buffer.push(&build_update("2023-05-01T10:00:00Z")).await.unwrap();
+        buffer.replace(&[]).await.unwrap();
+
+        assert!(buffer.is_empty());
+        assert!(!path.exists());
+        assert!(buffer.updates().is_empty());
+    }
+
+}
@@ -0,0 +1,167 @@
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+
+use tokio::fs;
+
+use crate::common::error::Error;
+use crate::server::config::RegionConfig;
+
+pub const DEFAULT_DATA_DIR: &str = ".watchdog";
+pub const DEFAULT_BUFFER_SIZE: usize = 1000;
+
+const INITIAL_RETRY_DELAY_MS: u64 = 1000;
+const MAX_RETRY_DELAY_MS: u64 = 60000;
+
+pub struct RelayConf {
+
+    pub base_url: String,
+    pub token: String,
+    pub region_name: String,
+
+    /// Local directory used by the relay to keep data between restarts (such
+    /// as the results buffered while the server is unreachable)
+    pub data_dir: PathBuf,
+
+    /// Maximum number of region updates kept in the buffer, the oldest updates
+    /// are dropped once the buffer is full
+    pub buffer_size: usize,
+
+    /// Run the region tests once and stop the relay
+    pub once: bool,
+
+    /// Only display the test results, nothing is sent to the server
+    pub dry_run: bool,
+
+    /// Local configuration file used by dry runs instead of the server configuration
+    pub config_path: Option<String>
+
+}
+
+/// Load the last region configuration received from the server, the relay
+/// starts from this configuration when the server is unreachable
+pub async fn load_cached_region(path: &Path) -> Result<Option<RegionConfig>, Error> {
+
+    let content = match fs::read_to_string(path).await {
+        Ok(content) => content,
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+        Err(err) => return Err(Error::new(format!("Could not read cached configuration {}", path.display()), err))
+    };
+
+    serde_json::from_str::<RegionConfig>(&content)
+        .map(Some)
+        .map_err(|err| Error::new(format!("Could not decode cached configuration {}", path.display()), err))
+}
+
+pub async fn save_cached_region(path: &Path, region_config: &RegionConfig) -> Result<(), Error> {
+
+    if let Some(parent_dir) = path.parent() {
+        fs::create_dir_all(parent_dir).await
+            .map_err(|err| Error::new(format!("Could not create directory {}", parent_dir.display()), err))?;
+    }
+
+    let content = serde_json::to_string_pretty(region_config)
+        .map_err(|err| Error::new("Could not encode region configuration", err))?;
+
+    // Written in a temporary file first, an interrupted write must not
+    // corrupt the configuration used on the next start
+    let temporary_path = path.with_extension("tmp");
+    fs::write(&temporary_path, content).await
+        .map_err(|err| Error::new(format!("Could not write cached configuration {}", temporary_path.display()), err))?;
+    fs::rename(&temporary_path, path).await
+        .map_err(|err| Error::new(format!("Could not replace cached configuration {}", path.display()), err))?;
+
+    Ok(())
+}
+
+/// Exponential delay between configuration fetches while the server is unreachable
+pub struct RetryBackoff {
+    delay: Duration
+}
+
+impl RetryBackoff {
+
+    pub fn new() -> Self {
+
+        RetryBackoff {
+            delay: Duration::from_millis(INITIAL_RETRY_DELAY_MS)
+        }
+    }
+
+    /// Delay before the next attempt, doubled after each failed attempt
+    pub fn next_delay(&mut self) -> Duration {
+
+        let delay = self.delay;
+        self.delay = std::cmp::min(self.delay * 2, Duration::from_millis(MAX_RETRY_DELAY_MS));
+        delay
+    }
+
+    pub fn reset(&mut self) {
+        self.delay = Duration::from_millis(INITIAL_RETRY_DELAY_MS);
+    }
+
+}
+
+impl Default for RetryBackoff {
+
+    fn default() -> Self {
+        RetryBackoff::new()
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::server::config::{AlertSeverity, GroupConfig, ThresholdConfig};
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_cache_region_config() {
+
+        let path = std::env::temp_dir().join(format!("watchdog-{}-cache", std::process::id())).join("region-config.json");
+        let _ = std::fs::remove_file(&path);
+        assert!(load_cached_region(&path).await.unwrap().is_none());
+
+        let region_config = RegionConfig {
+            name: "region-north".to_string(),
+            interval_ms: 5000,
+            threshold_ms: 15000,
+            kuma_url: None,
+            concurrency: 4,
+            test_timeout_ms: 2000,
+            groups: vec![GroupConfig {
+                name: "default".to_string(),
+                threshold_ms: 20000,
+                thresholds: ThresholdConfig::default(),
+                severity: AlertSeverity::default(),
+                tests: vec!["ping 1.1.1.1".to_string()]
+            }]
+        };
+        save_cached_region(&path, &region_config).await.unwrap();
+
+        let cached_config = load_cached_region(&path).await.unwrap().unwrap();
+        assert_eq!(cached_config.name, "region-north");
+        assert_eq!(cached_config.concurrency, 4);
+        assert_eq!(cached_config.groups[0].tests, vec!["ping 1.1.1.1"]);
+    }
+
+    #[test]
+    fn should_double_retry_delay() {
+
+        let mut backoff = RetryBackoff::new();
+
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(2));
+        assert_eq!(backoff.next_delay(), Duration::from_secs(4));
+
+        for _ in 0..10 {
+            backoff.next_delay();
+        }
+        assert_eq!(backoff.next_delay(), Duration::from_secs(60));
+
+        backoff.reset();
+        assert_eq!(backoff.next_delay(), Duration::from_secs(1));
+    }
+
+}
@@ -0,0 +1,51 @@
+use ansi_term::{Colour, Style};
+
+use crate::relay::model::{FailureReason, GroupResultInput, ResultCategory};
+
+/// Display the results of a relay cycle, used by the relay in dry-run mode
+pub fn display_group_results(group_results: &[GroupResultInput]) {
+
+    let bold = Style::new().bold();
+
+    println!();
+    for group in group_results {
+
+        println!("{}", bold.paint(format!("Group {}", group.name)));
+
+        for outcome in &group.tests {
+
+            // Metrics are reported by group, with the test target as label
+            let mut metrics: Vec<(String, f32)> = group.metrics.iter()
+                .filter(|metric| metric.labels.get("test_target") == Some(&outcome.target))
+                .map(|metric| (metric.name.clone(), metric.metric))
+                .collect();
+            metrics.sort_by(|first, second| first.0.cmp(&second.0));
+
+            display_outcome(&outcome.test, outcome.result, outcome.duration_ms, outcome.reason.as_ref(), outcome.error.as_deref(), &metrics);
+        }
+        println!();
+    }
+}
+
+/// Display the outcome of a single test, used by the relay dry runs and the CLI
+/// test and probe commands
+pub fn display_outcome(test: &str, result: ResultCategory, duration_ms: u64, reason: Option<&FailureReason>, message: Option<&str>, metrics: &[(String, f32)]) {
+
+    let result_label = match result {
+        ResultCategory::Success => Colour::Green.paint("✓ SUCCESS"),
+        ResultCategory::Warning => Colour::Yellow.paint("! WARNING"),
+        ResultCategory::Fail => Colour::Red.paint("✗ FAIL   ")
+    };
+
+    println!(" {}  {} ({}ms)", result_label, test, duration_ms);
+
+    if let Some(reason) = reason {
+        println!("     Reason    {}", reason);
+    }
+    if let Some(message) = message {
+        println!("     Details   {}", message);
+    }
+    for (metric_name, metric_value) in metrics {
+        println!("     Metric    {} = {}", metric_name, metric_value);
+    }
+}
@@ -1,5 +1,8 @@
 pub mod service;
 pub mod model;
+pub mod config;
+pub mod display;
+pub mod test;
 
-mod test;
 mod api;
+mod buffer;
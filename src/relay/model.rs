@@ -1,9 +1,9 @@
-use std::collections::HashMap;
+use std::{collections::HashMap, fmt};
 
 use serde::{Deserialize, Serialize};
 use validator::Validate;
 
-#[derive(Deserialize, Serialize, Validate)]
+#[derive(Deserialize, Serialize, Validate, Clone)]
 pub struct MetricInput {
 
     pub name: String,
@@ -14,7 +14,7 @@ pub struct MetricInput {
 
 }
 
-#[derive(Deserialize, Serialize, Validate)]
+#[derive(Deserialize, Serialize, Validate, Clone)]
 pub struct GroupResultInput {
 
     #[validate(length(max = 250))]
@@ -32,17 +32,137 @@ pub struct GroupResultInput {
     #[serde(default)]
     pub error_detail: Option<String>,
 
+    #[serde(default)]
+    pub tests: Vec<TestOutcomeInput>,
+
     pub metrics: Vec<MetricInput>
 
 }
 
-#[derive(PartialEq, Debug)]
+#[derive(Deserialize, Serialize, Validate, Clone)]
+pub struct TestOutcomeInput {
+
+    pub test: String,
+
+    pub target: String,
+
+    pub result: ResultCategory,
+
+    pub duration_ms: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub reason: Option<FailureReason>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub error: Option<String>
+
+}
+
+/// Test categories are ordered by severity, so that the worst category of
+/// several checks can be found with 'max'
+#[derive(Deserialize, Serialize, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Debug)]
+#[serde(rename_all = "lowercase")]
 pub enum ResultCategory {
     Success,
     Warning,
     Fail
 }
 
+/// Result of an on-demand probe, sent by the relay once the probe test is executed
+#[derive(Deserialize, Serialize, Validate, Clone)]
+pub struct ProbeResultInput {
+
+    pub target: String,
+
+    pub result: ResultCategory,
+
+    pub duration_ms: u64,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub reason: Option<FailureReason>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub error: Option<String>,
+
+    #[serde(default)]
+    pub metrics: HashMap<String, f32>
+
+}
+
+/// On-demand test requested from the CLI, handed to the relay in the response
+/// of a region update
+#[derive(Deserialize, Serialize, Clone)]
+pub struct ProbeRequest {
+
+    pub id: u32,
+
+    pub test: String
+
+}
+
+/// Server response to a region update
+#[derive(Deserialize, Serialize)]
+pub struct RegionUpdateOutput {
+
+    pub result: bool,
+
+    #[serde(default)]
+    pub probes: Vec<ProbeRequest>
+
+}
+
+/// Region update sent again to the server once reachable, with the original
+/// update timestamp (RFC 3339)
+#[derive(Deserialize, Serialize, Validate, Clone)]
+pub struct RegionBackfillInput {
+
+    pub timestamp: String,
+
+    pub groups: Vec<GroupResultInput>
+
+}
+
+/// Cause of a failed (or degraded) test, the test message gives more details
+/// about the failure when available
+#[derive(Deserialize, Serialize, Clone, PartialEq, Debug)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum FailureReason {
+    DnsResolution,
+    ConnectionRefused,
+    Unreachable,
+    Timeout,
+    Tls,
+    HttpStatus { status: u16 },
+    BodyMismatch,
+    UnexpectedAnswer,
+    PacketLoss { loss_pct: f32 },
+    Latency { latency_ms: f32 }
+}
+
+impl fmt::Display for FailureReason {
+
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+
+        match self {
+            FailureReason::DnsResolution => write!(f, "DNS resolution failed"),
+            FailureReason::ConnectionRefused => write!(f, "connection refused"),
+            FailureReason::Unreachable => write!(f, "host unreachable"),
+            FailureReason::Timeout => write!(f, "timeout"),
+            FailureReason::Tls => write!(f, "TLS error"),
+            FailureReason::HttpStatus { status } => write!(f, "HTTP status {}", status),
+            FailureReason::BodyMismatch => write!(f, "unexpected response body"),
+            FailureReason::UnexpectedAnswer => write!(f, "unexpected DNS answer"),
+            FailureReason::PacketLoss { loss_pct } => write!(f, "{:.0}% packet loss", loss_pct),
+            FailureReason::Latency { latency_ms } => write!(f, "latency of {:.0}ms", latency_ms)
+        }
+    }
+
+}
+
 #[derive(PartialEq, Debug)]
 pub struct TestResult {
 
@@ -50,7 +170,11 @@ pub struct TestResult {
 
     pub result: ResultCategory,
 
-    pub metrics: Option<HashMap<String, f32>>
+    pub metrics: Option<HashMap<String, f32>>,
+
+    pub reason: Option<FailureReason>,
+
+    pub message: Option<String>
 
 }
 
@@ -61,7 +185,9 @@ impl TestResult {
         TestResult {
             target: target_name.into(),
             result: ResultCategory::Fail,
-            metrics: None
+            metrics: None,
+            reason: None,
+            message: None
         }
     }
 
@@ -70,8 +196,22 @@ impl TestResult {
         TestResult {
             target: target_name.into(),
             result,
-            metrics
+            metrics,
+            reason: None,
+            message: None
         }
     }
 
+    pub fn with_reason(mut self, reason: FailureReason) -> TestResult {
+
+        self.reason = Some(reason);
+        self
+    }
+
+    pub fn with_message<M>(mut self, message: M) -> TestResult where M: Into<String> {
+
+        self.message = Some(message.into());
+        self
+    }
+
 }
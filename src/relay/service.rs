@@ -1,136 +1,184 @@
 use std::collections::HashMap;
+use std::path::Path;
+use std::sync::Arc;
 
+use chrono::Utc;
 use tokio::signal;
+use tokio::sync::Semaphore;
 use tokio::task;
-use tokio::time::{sleep, Duration};
+use tokio::time::{sleep, sleep_until, timeout_at, Duration, Instant};
 use tokio_util::sync::CancellationToken;
 
-use crate::relay::model::{GroupResultInput, MetricInput, ResultCategory};
+use crate::relay::model::{FailureReason, GroupResultInput, MetricInput, ProbeRequest, ProbeResultInput, RegionBackfillInput, ResultCategory, TestOutcomeInput, TestResult};
+use crate::server::config::{Config, GroupConfig, RegionConfig, ThresholdConfig};
 use crate::common::error::Error;
 
-use super::test::runner::TestRunner;
-use super::api::ServerApi;
+use super::display::display_group_results;
+use super::test::{runner::TestRunner, utils::split_components};
+use super::api::{BackfillError, ServerApi};
+use super::buffer::ResultBuffer;
+use super::config::{load_cached_region, save_cached_region, RelayConf, RetryBackoff};
 
-pub async fn launch(base_url: String, token: String, region_name: String) -> Result<(), Error> {
+const REPLAY_BATCH_SIZE: usize = 50;
+
+pub async fn launch(relay_conf: RelayConf) -> Result<(), Error> {
+
+    launch_with_runner(relay_conf, TestRunner::new()).await
+}
+
+/// Launch the relay with a custom test runner, programs embedding the relay can
+/// register their own test types next to the built-in tests
+pub async fn launch_with_runner(relay_conf: RelayConf, runner: TestRunner) -> Result<(), Error> {
 
     let cancel_token = CancellationToken::new();
     let cancel_token_task = cancel_token.clone();
 
-    let scheduler_task = task::spawn(async move {
+    let runner = Arc::new(runner);
+
+    // Region updates that could not be sent are kept on disk (and survive a relay
+    // restart) until the server is reachable again
+    let buffer_path = relay_conf.data_dir.join(format!("{}-buffer.jsonl", relay_conf.region_name));
+    let mut buffer = ResultBuffer::open(&buffer_path, relay_conf.buffer_size).await?;
+
+    let cache_path = relay_conf.data_dir.join(format!("{}-config.json", relay_conf.region_name));
 
-        let runner = TestRunner::new();
-        let api = ServerApi::new(&base_url, &token, &region_name);
+    // Dry runs are used to debug a configuration, the relay must fail fast (without
+    // retries or cache) when the configuration can not be loaded
+    let dry_run_config = if relay_conf.dry_run {
+        Some(load_dry_run_config(&relay_conf, &runner).await?)
+    } else {
+        None
+    };
 
-        let mut region_config = match api.fetch_region_conf().await {
-            Ok(config) => config,
-            Err(err) => err.exit(
-                "Could not fetch configuration from Watchdog API",
-                "Check your token and region name"
-            )
+    let mut scheduler_task = task::spawn(async move {
+
+        let api = ServerApi::new(&relay_conf.base_url, &relay_conf.token, &relay_conf.region_name);
+        let mut backoff = RetryBackoff::new();
+
+        let initial_config = match dry_run_config {
+            Some(region_config) => Some((region_config, false)),
+            None => fetch_initial_config(&api, &cache_path, &mut backoff, &cancel_token_task).await
+        };
+
+        let (mut region_config, from_cache) = match initial_config {
+            Some(initial_config) => initial_config,
+            None => return
         };
 
         println!();
         println!(" ✓ Watchdog relay is now UP");
         println!(" ✓ Found {} group(s) with a {}ms refresh interval", region_config.groups.len(), region_config.interval_ms, );
+        if from_cache {
+            println!(" ✓ Started from the cached configuration, the server is unreachable");
+        }
         println!();
 
         let mut last_update = String::new();
 
+        // A configuration fetch is pending when the relay started from the cached
+        // configuration, or when the server reports a new configuration version
+        let mut pending_reload = from_cache;
+        let mut next_reload = Instant::now();
+
         loop {
-            
+
+            // Tests are executed concurrently, and the region state is always sent by the end
+            // of the interval: slow targets must not delay the heartbeat sent to the server
+            let cycle_deadline = Instant::now() + Duration::from_millis(region_config.interval_ms);
+            let region_executions = execute_region_tests(&runner, &region_config, cycle_deadline).await;
+
             let mut group_results: Vec<GroupResultInput> = vec![];
             let mut last_kuma_ping: Option<f32> = None;
 
-            for group in &region_config.groups {
+            for (group, executions) in region_config.groups.iter().zip(region_executions) {
+                group_results.push(build_group_result(group, executions, &mut last_kuma_ping));
+            }
+            let update_timestamp = Utc::now();
+            
+            // Dry runs only display the results, nothing is sent to the server
+            if relay_conf.dry_run {
+                display_group_results(&group_results);
+            } else {
+
+                let update_result = api.update_region_state(&group_results, &last_update).await;
+                match update_result {
+                    Ok(region_update) => {
+
+                        // Probes are executed in the background, a slow probe must not
+                        // delay the next region update
+                        let test_timeout = Duration::from_millis(region_config.test_timeout_ms);
+                        for probe in region_update.probes {
+                            task::spawn(execute_probe(api.clone(), runner.clone(), probe, test_timeout));
+                        }
+
+                        if let Some(watchdog_update) = region_update.version {
 
-                // Each monitoring group in a region has multiple tests (ping, http, ...) to ensure
-                // that the group is properly working. A group is working only if ALL tests are working
-                // and can have warnings.
-    
-                let mut is_group_working = true;
-                let mut has_group_warnings: bool = false;
-                let mut error_message = None;
-                let mut error_detail = None;
+                            if !last_update.is_empty() {
+                                pending_reload = true;
+                            }
 
-                let mut group_metrics: Vec<MetricInput> = vec![];
+                            last_update = watchdog_update;
+                        }
 
-                for test_cmd in &group.tests {
+                        if pending_reload && Instant::now() >= next_reload {
 
-                    let test_result = runner.execute_test(test_cmd).await;
+                            match api.fetch_region_conf().await {
+                                Ok(fetched_config) => {
 
-                    match test_result {
-                        Ok(test) => {
+                                    save_cached_region(&cache_path, &fetched_config).await.unwrap_or_else(|err| {
+                                        eprintln!("Could not cache region configuration: {}", err);
+                                    });
 
-                            if test.result == ResultCategory::Fail {
-                                // TODO We only register the last fail
-                                error_message = Some(format!("test '{}' failed", test_cmd));
-                                is_group_working = false;
-                            }
-                            else if test.result == ResultCategory::Warning {
-                                has_group_warnings = true;
-                            }
+                                    region_config = fetched_config;
+                                    pending_reload = false;
+                                    backoff.reset();
+                                    println!("Relay config reloaded - version {}", last_update);
 
-                            for (metric_key, metric_value) in test.metrics.unwrap_or_default() {
+                                },
+                                Err(fetch_err) => {
 
-                                if metric_key == "ping_rtt" {
-                                    last_kuma_ping = Some(metric_value);
+                                    let retry_delay = backoff.next_delay();
+                                    next_reload = Instant::now() + retry_delay;
+                                    eprintln!("Could not reload relay config, retrying in {}ms: {}", retry_delay.as_millis(), fetch_err);
                                 }
-
-                                group_metrics.push(MetricInput {
-                                    name: metric_key,
-                                    labels: HashMap::from([
-                                        ("test_target".into(), test.target.to_string())
-                                    ]),
-                                    metric: metric_value
-                                });
                             }
+                        }
 
-                        },
-                        Err(err) => {
-                            eprintln!("{}", err);
-                            is_group_working = false;
-                            error_message = Some(err.message);
-                            error_detail = err.details;
+                        // The server is reachable again, the updates sent during the outage
+                        // are replayed (the server does not consider them as heartbeats)
+                        if !buffer.is_empty() {
+                            replay_buffer(&api, &mut buffer, cycle_deadline).await.unwrap_or_else(|err| {
+                                eprintln!("Could not replay buffered region updates: {}", err);
+                            });
                         }
+
+                    },
+                    Err(update_err) => {
+                        eprintln!("{}", update_err);
+
+                        let buffered_update = RegionBackfillInput {
+                            timestamp: update_timestamp.to_rfc3339(),
+                            groups: group_results.clone()
+                        };
+                        buffer.push(&buffered_update).await.unwrap_or_else(|err| {
+                            eprintln!("Could not buffer region update: {}", err);
+                        });
                     }
                 }
 
-                group_results.push(GroupResultInput {
-                    name: group.name.clone(),
-                    working: is_group_working,
-                    has_warnings: has_group_warnings,
-                    error_message,
-                    error_detail,
-                    metrics: group_metrics
-                });
-            }
-            
-            let update_result = api.update_region_state(&group_results, &last_update).await;
-            match update_result {
-                Ok(Some(watchdog_update)) => {
-
-                    if !last_update.is_empty() {
-                        region_config = api.fetch_region_conf().await.unwrap();
-                        println!("Relay config reloaded - version {}", last_update);
-                    }
+                if let Some(kuma_url) = &region_config.kuma_url {
 
-                    last_update = watchdog_update;
+                    let total_groups = group_results.len();
+                    let unstable_groups = group_results.iter().filter(|x| x.has_warnings || !x.working).count();
 
-                },
-                Err(update_err) => {
-                    eprintln!("{}", update_err);
-                },
-                _ => {}
+                    api.trigger_kuma_update(kuma_url, total_groups, unstable_groups, last_kuma_ping).await.unwrap_or_else(|err| {
+                        eprintln!("Error while triggering Kuma update: {}", err);
+                    });
+                }
             }
 
-            if let Some(kuma_url) = &region_config.kuma_url {
-
-                let total_groups = group_results.len();
-                let unstable_groups = group_results.iter().filter(|x| x.has_warnings || !x.working).count();
-
-                api.trigger_kuma_update(kuma_url, total_groups, unstable_groups, last_kuma_ping).await.unwrap_or_else(|err| {
-                    eprintln!("Error while triggering Kuma update: {}", err);
-                });
+            if relay_conf.once {
+                break;
             }
 
             let mut cancel_loop = false;
@@ -139,7 +187,7 @@ pub async fn launch(base_url: String, token: String, region_name: String) -> Res
                 _ = cancel_token_task.cancelled() => {
                     cancel_loop = true;
                 }
-                _ = sleep(Duration::from_millis(region_config.interval_ms)) => {
+                _ = sleep_until(cycle_deadline) => {
                     // Sleep went well... on to the next tests
                 }
             };
@@ -151,11 +199,579 @@ pub async fn launch(base_url: String, token: String, region_name: String) -> Res
 
     });
 
-    signal::ctrl_c().await.map_err(|err| Error::new("Could not handle graceful shutdown signal", err))?;
-    cancel_token.cancel();
-    println!("Received graceful shutdown signal");
+    // The scheduler task only ends by itself when the relay runs once
+    tokio::select! {
+        signal_result = signal::ctrl_c() => {
+
+            signal_result.map_err(|err| Error::new("Could not handle graceful shutdown signal", err))?;
+            cancel_token.cancel();
+            println!("Received graceful shutdown signal");
+
+            scheduler_task.await.map_err(|err| Error::new("Could not end scheduler task", err))?;
+        }
+        task_result = &mut scheduler_task => {
+            task_result.map_err(|err| Error::new("Could not end scheduler task", err))?;
+        }
+    };
+
+    Ok(())
+}
+
+/// Load the region configuration of a dry run, from a local configuration file
+/// (validated with the relay tests) or from the server
+async fn load_dry_run_config(relay_conf: &RelayConf, runner: &TestRunner) -> Result<RegionConfig, Error> {
+
+    match &relay_conf.config_path {
+        Some(config_path) => {
+
+            let config = Config::new(config_path, runner).await?;
+            config.export_region(&relay_conf.region_name).cloned()
+                .ok_or_else(|| Error::basic(format!("Region '{}' not found in configuration {}", relay_conf.region_name, config_path)))
+
+        },
+        None => {
+
+            let api = ServerApi::new(&relay_conf.base_url, &relay_conf.token, &relay_conf.region_name);
+            api.fetch_region_conf().await
+        }
+    }
+}
+
+/**
+ * Fetch the region configuration on startup. When the server is unreachable, the relay
+ * starts from the last cached configuration (if any) and otherwise retries with an
+ * exponential backoff. Returns the configuration and whether it comes from the cache,
+ * or nothing when the relay is stopped before receiving a configuration.
+ */
+async fn fetch_initial_config(api: &ServerApi, cache_path: &Path, backoff: &mut RetryBackoff, cancel_token: &CancellationToken) -> Option<(RegionConfig, bool)> {
+
+    loop {
+
+        let fetch_err = match api.fetch_region_conf().await {
+            Ok(region_config) => {
+
+                save_cached_region(cache_path, &region_config).await.unwrap_or_else(|err| {
+                    eprintln!("Could not cache region configuration: {}", err);
+                });
+                return Some((region_config, false));
+
+            },
+            Err(fetch_err) => fetch_err
+        };
+
+        eprintln!("Could not fetch configuration from Watchdog API: {}", fetch_err);
+        if let Some(details) = &fetch_err.details {
+            eprintln!("{}", details);
+        }
+
+        match load_cached_region(cache_path).await {
+            Ok(Some(cached_config)) => return Some((cached_config, true)),
+            Ok(None) => {},
+            Err(cache_err) => eprintln!("{}", cache_err)
+        }
+
+        let retry_delay = backoff.next_delay();
+        eprintln!("No cached configuration found, retrying in {}ms (check your token and region name)", retry_delay.as_millis());
+
+        tokio::select! {
+            _ = cancel_token.cancelled() => {
+                return None;
+            }
+            _ = sleep(retry_delay) => {}
+        };
+    }
+}
+
+/// Replay the buffered updates by batches, from the oldest to the most recent one. The
+/// replay stops at the end of the interval (after at least one batch) or when the server is unavailable, the remaining
+/// updates are kept in the buffer for the next successful update. Batches rejected by the
+/// server are dropped, they would otherwise block the newer updates forever.
+async fn replay_buffer(api: &ServerApi, buffer: &mut ResultBuffer, deadline: Instant) -> Result<(), Error> {
+
+    let updates = buffer.updates();
+
+    let mut processed_count = 0;
+    let mut dropped_count = 0;
+    for batch in updates.chunks(REPLAY_BATCH_SIZE) {
+
+        // Slow tests may use the whole interval, at least one batch is sent per
+        // cycle in order to drain the buffer whatever the test durations
+        if processed_count > 0 && Instant::now() >= deadline {
+            break;
+        }
+
+        match api.backfill_region_state(batch).await {
+            Ok(()) => {},
+            Err(BackfillError::Rejected(rejection_err)) => {
+                eprintln!("Dropping {} buffered region update(s) rejected by the server: {}", batch.len(), rejection_err);
+                dropped_count += batch.len();
+            },
+            Err(BackfillError::Unavailable(replay_err)) => {
+                buffer.replace(&updates[processed_count..]).await?;
+                return Err(replay_err);
+            }
+        }
+        processed_count += batch.len();
+    }
+
+    if processed_count == 0 {
+        return Ok(());
+    }
 
-    scheduler_task.await.map_err(|err| Error::new("Could not end scheduler task", err))?;
+    buffer.replace(&updates[processed_count..]).await?;
+    println!("Replayed {} buffered region update(s), {} dropped, {} remaining", processed_count - dropped_count, dropped_count, buffer.len());
 
     Ok(())
 }
+
+/// A test executed by the relay, with the time spent running the test
+struct TestExecution {
+    test_cmd: String,
+    result: Result<TestResult, Error>,
+    duration: Duration
+}
+
+/// Execute all tests of a region concurrently (within the concurrency limit of the region),
+/// the results are returned per group in the same order as the configuration
+async fn execute_region_tests(runner: &Arc<TestRunner>, region_config: &RegionConfig, deadline: Instant) -> Vec<Vec<TestExecution>> {
+
+    let semaphore = Arc::new(Semaphore::new(region_config.concurrency));
+    let test_timeout = Duration::from_millis(region_config.test_timeout_ms);
+
+    let mut region_handles = vec![];
+    for group in &region_config.groups {
+
+        let group_handles: Vec<_> = group.tests.iter().map(|test_cmd| {
+
+            let runner = runner.clone();
+            let semaphore = semaphore.clone();
+            let test_cmd = test_cmd.clone();
+            let thresholds = group.thresholds.clone();
+
+            task::spawn(async move {
+                execute_with_timeout(&runner, &semaphore, test_cmd, &thresholds, test_timeout, deadline).await
+            })
+
+        }).collect();
+
+        region_handles.push(group_handles);
+    }
+
+    let mut region_executions = vec![];
+    for (group, group_handles) in region_config.groups.iter().zip(region_handles) {
+
+        let mut executions = vec![];
+        for (test_cmd, handle) in group.tests.iter().zip(group_handles) {
+
+            let execution = handle.await.unwrap_or_else(|err| TestExecution {
+                test_cmd: test_cmd.clone(),
+                result: Err(Error::new("Test task failed", err)),
+                duration: Duration::ZERO
+            });
+            executions.push(execution);
+        }
+
+        region_executions.push(executions);
+    }
+
+    region_executions
+}
+
+/// Execute a test once a concurrency slot is available, the test is stopped and
+/// considered as failed after the test timeout or the end of the interval
+async fn execute_with_timeout(runner: &TestRunner, semaphore: &Semaphore, test_cmd: String, thresholds: &ThresholdConfig, test_timeout: Duration, deadline: Instant) -> TestExecution {
+
+    let _permit = match timeout_at(deadline, semaphore.acquire()).await {
+        Ok(Ok(permit)) => permit,
+        _ => {
+            let result = TestResult::fail(command_target(&test_cmd))
+                .with_reason(FailureReason::Timeout)
+                .with_message("not executed before the end of the interval");
+            return TestExecution { test_cmd, result: Ok(result), duration: Duration::ZERO };
+        }
+    };
+
+    let test_start = Instant::now();
+    let test_deadline = std::cmp::min(test_start + test_timeout, deadline);
+
+    let result = match timeout_at(test_deadline, runner.execute_test(&test_cmd, thresholds)).await {
+        Ok(test_result) => test_result,
+        Err(_elapsed) => {
+            let message = format!("timed out after {}ms", test_deadline.duration_since(test_start).as_millis());
+            Ok(TestResult::fail(command_target(&test_cmd)).with_reason(FailureReason::Timeout).with_message(message))
+        }
+    };
+
+    TestExecution {
+        test_cmd,
+        result,
+        duration: test_start.elapsed()
+    }
+}
+
+/// Execute an on-demand probe requested from the CLI and send its result to the server
+async fn execute_probe(api: ServerApi, runner: Arc<TestRunner>, probe: ProbeRequest, test_timeout: Duration) {
+
+    println!("Executing probe {} ({})", probe.id, probe.test);
+
+    let test_start = Instant::now();
+    let test_result = match timeout_at(test_start + test_timeout, runner.execute_test(&probe.test, &ThresholdConfig::default())).await {
+        Ok(test_result) => test_result,
+        Err(_elapsed) => {
+            let message = format!("timed out after {}ms", test_timeout.as_millis());
+            Ok(TestResult::fail(command_target(&probe.test)).with_reason(FailureReason::Timeout).with_message(message))
+        }
+    };
+    let duration_ms = test_start.elapsed().as_millis() as u64;
+
+    let probe_result = match test_result {
+        Ok(test) => ProbeResultInput {
+            target: test.target,
+            result: test.result,
+            duration_ms,
+            reason: test.reason,
+            error: test.message,
+            metrics: test.metrics.unwrap_or_default()
+        },
+        Err(test_err) => ProbeResultInput {
+            target: command_target(&probe.test),
+            result: ResultCategory::Fail,
+            duration_ms,
+            reason: None,
+            error: Some(test_err.to_string()),
+            metrics: HashMap::new()
+        }
+    };
+
+    api.send_probe_result(probe.id, &probe_result).await.unwrap_or_else(|err| {
+        eprintln!("Could not send result of probe {}: {}", probe.id, err);
+    });
+}
+
+fn command_target(test_cmd: &str) -> String {
+
+    split_components(test_cmd).get(1).cloned().unwrap_or_default()
+}
+
+/// Build the group state from its test executions: a group is working only if ALL
+/// tests are working, and can have warnings. The outcome of each test is reported
+/// to the server, with the reason of the first failure in the group error message
+/// and its details in the group error detail.
+fn build_group_result(group: &GroupConfig, executions: Vec<TestExecution>, last_kuma_ping: &mut Option<f32>) -> GroupResultInput {
+
+    let mut test_outcomes: Vec<TestOutcomeInput> = vec![];
+    let mut test_details: Vec<Option<String>> = vec![];
+    let mut group_metrics: Vec<MetricInput> = vec![];
+
+    for execution in executions {
+
+        let duration_ms = execution.duration.as_millis() as u64;
+
+        match execution.result {
+            Ok(test) => {
+
+                for (metric_key, metric_value) in test.metrics.unwrap_or_default() {
+
+                    if metric_key == "ping_rtt" {
+                        *last_kuma_ping = Some(metric_value);
+                    }
+
+                    group_metrics.push(MetricInput {
+                        name: metric_key,
+                        labels: HashMap::from([
+                            ("test_target".into(), test.target.to_string())
+                        ]),
+                        metric: metric_value
+                    });
+                }
+
+                // The test message details the failure when a reason is known
+                test_details.push(test.reason.as_ref().and(test.message.clone()));
+                test_outcomes.push(TestOutcomeInput {
+                    test: execution.test_cmd,
+                    target: test.target,
+                    result: test.result,
+                    duration_ms,
+                    reason: test.reason,
+                    error: test.message
+                });
+
+            },
+            Err(err) => {
+                eprintln!("{}", err);
+
+                test_details.push(err.details);
+                test_outcomes.push(TestOutcomeInput {
+                    target: command_target(&execution.test_cmd),
+                    test: execution.test_cmd,
+                    result: ResultCategory::Fail,
+                    duration_ms,
+                    reason: None,
+                    error: Some(err.message)
+                });
+            }
+        }
+    }
+
+    let failed_count = test_outcomes.iter().filter(|outcome| outcome.result == ResultCategory::Fail).count();
+    let has_group_warnings = test_outcomes.iter().any(|outcome| outcome.result == ResultCategory::Warning);
+
+    // Warnings with a cause (such as an HTTP body mismatch) are reported to
+    // the server when no test has failed
+    let reported_index = test_outcomes.iter().position(|outcome| outcome.result == ResultCategory::Fail)
+        .or_else(|| test_outcomes.iter().position(|outcome| {
+            outcome.result == ResultCategory::Warning && (outcome.reason.is_some() || outcome.error.is_some())
+        }));
+
+    let (error_message, error_detail) = match reported_index {
+        Some(index) => {
+
+            let outcome = &test_outcomes[index];
+            let cause = outcome.reason.as_ref().map(|reason| reason.to_string()).or_else(|| outcome.error.clone());
+
+            let mut message = match (outcome.result, cause) {
+                (ResultCategory::Fail, Some(cause)) => format!("test '{}' failed ({})", outcome.test, cause),
+                (ResultCategory::Fail, None) => format!("test '{}' failed", outcome.test),
+                (_, cause) => format!("test '{}' has warnings ({})", outcome.test, cause.unwrap_or_default())
+            };
+            if failed_count > 1 {
+                message.push_str(&format!(" and {} other test(s) failed", failed_count - 1));
+            }
+
+            (Some(message), test_details.swap_remove(index))
+
+        },
+        None => (None, None)
+    };
+
+    GroupResultInput {
+        name: group.name.clone(),
+        working: failed_count == 0,
+        has_warnings: has_group_warnings,
+        error_message,
+        error_detail,
+        tests: test_outcomes,
+        metrics: group_metrics
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use async_trait::async_trait;
+
+    use crate::relay::test::{runner::Test, utils::matches_type};
+    use crate::server::config::{parse_to_milliseconds, AlertSeverity};
+
+    use super::*;
+
+    /// Test waiting for the given duration, such as 'sleep 200ms'
+    struct SleepTest {}
+
+    #[async_trait]
+    impl Test for SleepTest {
+
+        fn matches(&self, test: &str) -> bool {
+
+            matches_type(test, "sleep")
+        }
+
+        fn parse(&self, _test: &str) -> Result<(), Error> {
+
+            Ok(())
+        }
+
+        async fn execute(&self, test: &str, _thresholds: &ThresholdConfig) -> Result<TestResult, Error> {
+
+            let duration = split_components(test).get(1).cloned().unwrap_or_default();
+            tokio::time::sleep(Duration::from_millis(parse_to_milliseconds(&duration).unwrap())).await;
+
+            Ok(TestResult::build(duration, ResultCategory::Success, None))
+        }
+
+    }
+
+    fn build_sleep_region(concurrency: usize, test_timeout_ms: u64, tests: Vec<&str>) -> (Arc<TestRunner>, RegionConfig) {
+
+        let mut runner = TestRunner::empty();
+        runner.register(SleepTest {});
+
+        let region_config = RegionConfig {
+            name: "local".to_string(),
+            interval_ms: 10000,
+            threshold_ms: 31000,
+            kuma_url: None,
+            concurrency,
+            test_timeout_ms,
+            groups: vec![GroupConfig {
+                name: "default".to_string(),
+                threshold_ms: 31000,
+                thresholds: ThresholdConfig::default(),
+                severity: AlertSeverity::default(),
+                tests: tests.into_iter().map(String::from).collect()
+            }]
+        };
+
+        (Arc::new(runner), region_config)
+    }
+
+    // Tests measuring durations run with a paused clock: time only advances when all
+    // tasks are waiting, the assertions do not depend on the machine load
+
+    #[tokio::test(start_paused = true)]
+    async fn should_run_tests_concurrently() {
+
+        let (runner, region_config) = build_sleep_region(4, 5000, vec!["sleep 200ms"; 4]);
+
+        let start = Instant::now();
+        let region_executions = execute_region_tests(&runner, &region_config, start + Duration::from_secs(5)).await;
+
+        assert!(start.elapsed() < Duration::from_millis(400));
+        assert_eq!(region_executions[0].len(), 4);
+        assert!(region_executions[0].iter().all(|execution| execution.result.as_ref().unwrap().result == ResultCategory::Success));
+        assert!(region_executions[0].iter().all(|execution| execution.duration >= Duration::from_millis(200)));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_fail_tests_after_timeout() {
+
+        let (runner, region_config) = build_sleep_region(4, 100, vec!["sleep 50ms", "sleep 2s"]);
+
+        let start = Instant::now();
+        let region_executions = execute_region_tests(&runner, &region_config, start + Duration::from_secs(5)).await;
+
+        assert!(start.elapsed() < Duration::from_millis(200));
+        assert_eq!(region_executions[0][0].result.as_ref().unwrap().result, ResultCategory::Success);
+        assert_eq!(region_executions[0][1].result, Ok(TestResult::fail("2s").with_reason(FailureReason::Timeout).with_message("timed out after 100ms")));
+    }
+
+    #[tokio::test(start_paused = true)]
+    async fn should_fail_tests_after_interval() {
+
+        let (runner, region_config) = build_sleep_region(1, 5000, vec!["sleep 300ms", "sleep 301ms", "sleep 302ms"]);
+
+        let start = Instant::now();
+        let region_executions = execute_region_tests(&runner, &region_config, start + Duration::from_millis(500)).await;
+
+        assert!(start.elapsed() < Duration::from_millis(600));
+        assert_eq!(region_executions[0][0].result.as_ref().unwrap().result, ResultCategory::Success);
+        assert_eq!(region_executions[0][1].result.as_ref().unwrap().result, ResultCategory::Fail);
+        assert_eq!(region_executions[0][2].result, Ok(TestResult::fail("302ms").with_reason(FailureReason::Timeout).with_message("not executed before the end of the interval")));
+    }
+
+    /// Launch a local server answering all backfill requests with the given status
+    /// code, and return its base URL
+    async fn launch_backfill_server(status: axum::http::StatusCode) -> String {
+
+        let app = axum::Router::new()
+            .route("/api/v1/relay/:region_name/backfill", axum::routing::post(move || async move { status }));
+
+        let listener = std::net::TcpListener::bind("127.0.0.1:0").unwrap();
+        let base_url = format!("http://{}", listener.local_addr().unwrap());
+
+        let server = axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service());
+        tokio::spawn(server);
+
+        base_url
+    }
+
+    async fn build_buffer(name: &str, update_count: usize) -> ResultBuffer {
+
+        let path = std::env::temp_dir().join(format!("watchdog-{}-{}", std::process::id(), name)).join("buffer.jsonl");
+        let _ = std::fs::remove_file(&path);
+
+        let mut buffer = ResultBuffer::open(&path, 1000).await.unwrap();
+        for _ in 0..update_count {
+            buffer.push(&RegionBackfillInput { timestamp: "2023-05-01T10:00:00Z".to_string(), groups: vec![] }).await.unwrap();
+        }
+        buffer
+    }
+
+    #[tokio::test]
+    async fn should_drop_rejected_updates() {
+
+        let base_url = launch_backfill_server(axum::http::StatusCode::NOT_FOUND).await;
+        let api = ServerApi::new(&base_url, "token", "removed-region");
+        let mut buffer = build_buffer("replay-rejected", REPLAY_BATCH_SIZE + 10).await;
+
+        replay_buffer(&api, &mut buffer, Instant::now() + Duration::from_secs(5)).await.unwrap();
+
+        assert!(buffer.is_empty());
+    }
+
+    #[tokio::test]
+    async fn should_replay_one_batch_after_deadline() {
+
+        let base_url = launch_backfill_server(axum::http::StatusCode::OK).await;
+        let api = ServerApi::new(&base_url, "token", "local");
+        let mut buffer = build_buffer("replay-deadline", REPLAY_BATCH_SIZE + 10).await;
+
+        // The tests used the whole interval, the oldest batch is still replayed
+        replay_buffer(&api, &mut buffer, Instant::now()).await.unwrap();
+
+        assert_eq!(buffer.len(), 10);
+    }
+
+    #[tokio::test]
+    async fn should_keep_updates_when_server_unavailable() {
+
+        let base_url = launch_backfill_server(axum::http::StatusCode::SERVICE_UNAVAILABLE).await;
+        let api = ServerApi::new(&base_url, "token", "local");
+        let mut buffer = build_buffer("replay-unavailable", 3).await;
+
+        assert!(replay_buffer(&api, &mut buffer, Instant::now() + Duration::from_secs(5)).await.is_err());
+        assert_eq!(buffer.len(), 3);
+    }
+
+    fn build_execution(test_cmd: &str, result: Result<TestResult, Error>) -> TestExecution {
+
+        TestExecution {
+            test_cmd: test_cmd.to_string(),
+            result,
+            duration: Duration::from_millis(10)
+        }
+    }
+
+    #[test]
+    fn should_report_test_outcomes() {
+
+        let (_, region_config) = build_sleep_region(1, 5000, vec![]);
+        let executions = vec![
+            build_execution("sleep 10ms", Ok(TestResult::build("10ms", ResultCategory::Success, None))),
+            build_execution("sleep 2s", Ok(TestResult::fail("2s").with_reason(FailureReason::Timeout).with_message("timed out after 100ms"))),
+            build_execution("sleep 3s", Ok(TestResult::fail("3s"))),
+            build_execution("unknown", Err(Error::new("Test 'unknown' failed, command not found", "no test type")))
+        ];
+
+        let group_result = build_group_result(&region_config.groups[0], executions, &mut None);
+
+        assert!(!group_result.working);
+        assert_eq!(group_result.error_message, Some("test 'sleep 2s' failed (timeout) and 2 other test(s) failed".to_string()));
+        assert_eq!(group_result.error_detail, Some("timed out after 100ms".to_string()));
+
+        assert_eq!(group_result.tests.len(), 4);
+        assert_eq!(group_result.tests[0].result, ResultCategory::Success);
+        assert_eq!(group_result.tests[1].target, "2s");
+        assert_eq!(group_result.tests[1].duration_ms, 10);
+        assert_eq!(group_result.tests[1].reason, Some(FailureReason::Timeout));
+        assert_eq!(group_result.tests[1].error, Some("timed out after 100ms".to_string()));
+        assert_eq!(group_result.tests[3].result, ResultCategory::Fail);
+        assert_eq!(group_result.tests[3].error, Some("Test 'unknown' failed, command not found".to_string()));
+    }
+
+    #[test]
+    fn should_report_warning_message() {
+
+        let (_, region_config) = build_sleep_region(1, 5000, vec![]);
+        let executions = vec![
+            build_execution("sleep 10ms", Ok(TestResult::build("10ms", ResultCategory::Warning, None).with_message("slow")))
+        ];
+
+        let group_result = build_group_result(&region_config.groups[0], executions, &mut None);
+
+        assert!(group_result.working);
+        assert!(group_result.has_warnings);
+        assert_eq!(group_result.error_message, Some("test 'sleep 10ms' has warnings (slow)".to_string()));
+    }
+
+}
@@ -0,0 +1,262 @@
+use regex::Regex;
+use serde_json::Value;
+
+use crate::common::error::Error;
+
+/// An assertion performed on a HTTP response body, the response is only
+/// considered valid when all assertions of a test are matching.
+pub enum BodyAssertion {
+    Contains(String),
+    Regex(Regex),
+    JsonPath(JsonPathAssertion)
+}
+
+pub struct JsonPathAssertion {
+    path: String,
+    segments: Vec<PathSegment>,
+    comparison: Option<(Operator, Value)>
+}
+
+enum PathSegment {
+    Key(String),
+    Index(usize)
+}
+
+#[derive(PartialEq)]
+enum Operator {
+    Equal,
+    NotEqual
+}
+
+impl BodyAssertion {
+
+    pub fn contains<M>(substring: M) -> Self where M: Into<String> {
+
+        BodyAssertion::Contains(substring.into())
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, Error> {
+
+        let regex = Regex::new(pattern)
+            .map_err(|err| Error::new("HTTP test failed", format!("Invalid body regex '{}' ({})", pattern, err)))?;
+
+        Ok(BodyAssertion::Regex(regex))
+    }
+
+    /**
+     * Parse a JSON path assertion such as '$.status == "ok"', '$.items[0].id != 3' or
+     * '$.db' (the path must exist). Expected values are parsed as JSON when possible
+     * and fallback to plain strings, single quotes can also be used for strings.
+     */
+    pub fn json_path(expression: &str) -> Result<Self, Error> {
+
+        let (path, comparison) = match find_operator(expression) {
+            Some((index, operator)) => {
+                let expected = parse_expected_value(&expression[index + 2..]);
+                (&expression[..index], Some((operator, expected)))
+            },
+            None => (expression, None)
+        };
+
+        let path = path.trim();
+        let segments = parse_path(path)?;
+
+        Ok(BodyAssertion::JsonPath(JsonPathAssertion {
+            path: path.to_string(),
+            segments,
+            comparison
+        }))
+    }
+
+    /// Check the assertion against a response body, the mismatch is returned as
+    /// a human-readable message when the assertion does not hold
+    pub fn check(&self, body: &str) -> Result<(), String> {
+
+        match self {
+            BodyAssertion::Contains(substring) => {
+                if body.contains(substring.as_str()) {
+                    return Ok(());
+                }
+                Err(format!("body does not contain '{}'", substring))
+            },
+            BodyAssertion::Regex(regex) => {
+                if regex.is_match(body) {
+                    return Ok(());
+                }
+                Err(format!("body does not match regex '{}'", regex.as_str()))
+            },
+            BodyAssertion::JsonPath(assertion) => assertion.check(body)
+        }
+    }
+
+}
+
+impl JsonPathAssertion {
+
+    fn check(&self, body: &str) -> Result<(), String> {
+
+        let json_body: Value = serde_json::from_str(body).map_err(|_| "body is not valid JSON".to_string())?;
+
+        let mut current = &json_body;
+        for segment in &self.segments {
+
+            let next = match segment {
+                PathSegment::Key(key) => current.get(key),
+                PathSegment::Index(index) => current.get(index)
+            };
+
+            current = next.ok_or_else(|| format!("JSON path {} not found in body", self.path))?;
+        }
+
+        match &self.comparison {
+            Some((operator, expected)) => {
+
+                let is_equal = current == expected;
+                match operator {
+                    Operator::Equal if !is_equal => Err(format!("expected {} == {}, found {}", self.path, expected, current)),
+                    Operator::NotEqual if is_equal => Err(format!("expected {} != {}", self.path, expected)),
+                    _ => Ok(())
+                }
+            },
+            None => Ok(())
+        }
+    }
+
+}
+
+fn parse_path(path: &str) -> Result<Vec<PathSegment>, Error> {
+
+    let invalid_path = || Error::new("HTTP test failed", format!("Invalid JSON path '{}'", path));
+
+    let mut remaining = path.strip_prefix('$').ok_or_else(invalid_path)?;
+    let mut segments: Vec<PathSegment> = vec![];
+
+    while !remaining.is_empty() {
+
+        if let Some(key_path) = remaining.strip_prefix('.') {
+
+            let key_end = key_path.find(['.', '[']).unwrap_or(key_path.len());
+            if key_end == 0 {
+                return Err(invalid_path());
+            }
+
+            segments.push(PathSegment::Key(key_path[..key_end].to_string()));
+            remaining = &key_path[key_end..];
+        }
+        else if let Some(index_path) = remaining.strip_prefix('[') {
+
+            let (index, rest) = index_path.split_once(']').ok_or_else(invalid_path)?;
+            let index = index.trim();
+
+            let quoted_key = index.strip_prefix('\'').and_then(|key| key.strip_suffix('\''))
+                .or_else(|| index.strip_prefix('"').and_then(|key| key.strip_suffix('"')));
+
+            match quoted_key {
+                Some(key) => segments.push(PathSegment::Key(key.to_string())),
+                None => segments.push(PathSegment::Index(index.parse::<usize>().map_err(|_| invalid_path())?))
+            }
+            remaining = rest;
+        }
+        else {
+            return Err(invalid_path());
+        }
+    }
+
+    Ok(segments)
+}
+
+/// Position of the first comparison operator ('==' or '!=') outside quoted strings
+fn find_operator(expression: &str) -> Option<(usize, Operator)> {
+
+    let mut quote: Option<char> = None;
+    for (index, character) in expression.char_indices() {
+
+        match quote {
+            Some(quote_char) if character == quote_char => quote = None,
+            Some(_) => {},
+            None if character == '"' || character == '\'' => quote = Some(character),
+            None if expression[index..].starts_with("==") => return Some((index, Operator::Equal)),
+            None if expression[index..].starts_with("!=") => return Some((index, Operator::NotEqual)),
+            None => {}
+        }
+    }
+
+    None
+}
+
+fn parse_expected_value(expected: &str) -> Value {
+
+    let expected = expected.trim();
+
+    if let Some(single_quoted) = expected.strip_prefix('\'').and_then(|value| value.strip_suffix('\'')) {
+        return Value::String(single_quoted.to_string());
+    }
+
+    serde_json::from_str::<Value>(expected).unwrap_or_else(|_| Value::String(expected.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    const HEALTH_BODY: &str = r#"{"status": "ok", "checks": [{"name": "db", "up": false}], "latency": 12}"#;
+
+    #[test]
+    fn should_check_substring() {
+
+        assert!(BodyAssertion::contains("\"ok\"").check(HEALTH_BODY).is_ok());
+        assert_eq!(BodyAssertion::contains("down").check(HEALTH_BODY), Err("body does not contain 'down'".to_string()));
+    }
+
+    #[test]
+    fn should_check_regex() {
+
+        assert!(BodyAssertion::regex("\"latency\": \\d+").unwrap().check(HEALTH_BODY).is_ok());
+        assert!(BodyAssertion::regex("^OK$").unwrap().check(HEALTH_BODY).is_err());
+        assert!(BodyAssertion::regex("(unclosed").is_err());
+    }
+
+    #[test]
+    fn should_compare_json_path() {
+
+        assert!(BodyAssertion::json_path("$.status == \"ok\"").unwrap().check(HEALTH_BODY).is_ok());
+        assert!(BodyAssertion::json_path("$.status == 'ok'").unwrap().check(HEALTH_BODY).is_ok());
+        assert!(BodyAssertion::json_path("$.latency == 12").unwrap().check(HEALTH_BODY).is_ok());
+        assert!(BodyAssertion::json_path("$.checks[0]['name'] != 'cache'").unwrap().check(HEALTH_BODY).is_ok());
+        assert!(BodyAssertion::json_path("$.checks[0].up").unwrap().check(HEALTH_BODY).is_ok());
+
+        assert_eq!(
+            BodyAssertion::json_path("$.checks[0].up == true").unwrap().check(HEALTH_BODY),
+            Err("expected $.checks[0].up == true, found false".to_string())
+        );
+        assert_eq!(
+            BodyAssertion::json_path("$.checks[1].up").unwrap().check(HEALTH_BODY),
+            Err("JSON path $.checks[1].up not found in body".to_string())
+        );
+        assert_eq!(
+            BodyAssertion::json_path("$.status").unwrap().check("not json"),
+            Err("body is not valid JSON".to_string())
+        );
+    }
+
+    #[test]
+    fn should_ignore_operators_in_strings() {
+
+        let body = r#"{"msg": "a!=b", "note": "x==y"}"#;
+
+        assert!(BodyAssertion::json_path("$.msg == \"a!=b\"").unwrap().check(body).is_ok());
+        assert!(BodyAssertion::json_path("$.note != 'a==b'").unwrap().check(body).is_ok());
+        assert!(BodyAssertion::json_path("$['msg'] == 'a!=b'").unwrap().check(body).is_ok());
+    }
+
+    #[test]
+    fn should_deny_invalid_json_path() {
+
+        assert!(BodyAssertion::json_path("status == 'ok'").is_err());
+        assert!(BodyAssertion::json_path("$..status").is_err());
+        assert!(BodyAssertion::json_path("$.items[first]").is_err());
+        assert!(BodyAssertion::json_path("$.items[0").is_err());
+    }
+
+}
@@ -1,6 +1,31 @@
-use std::str;
+use std::{str, collections::HashMap, net::{IpAddr, SocketAddr}, time::Duration};
+use tokio::time::Instant;
 
-use crate::{common::error::Error, relay::model::TestResult};
+use trust_dns_resolver::TokioAsyncResolver;
+use trust_dns_resolver::config::{NameServerConfigGroup, ResolverConfig, ResolverOpts};
+use trust_dns_resolver::error::ResolveErrorKind;
+use trust_dns_resolver::proto::op::ResponseCode;
+use trust_dns_resolver::proto::rr::{RData, RecordType};
+use trust_dns_resolver::system_conf::read_system_conf;
+use async_trait::async_trait;
+
+use crate::{common::error::Error, relay::model::{FailureReason, ResultCategory, TestResult}, server::config::{parse_to_milliseconds, ThresholdConfig}};
+
+use super::{runner::Test, utils::{latency_category, matches_type, split_components}};
+
+const DEFAULT_DNS_PORT: u16 = 53;
+const DEFAULT_DNS_TIMEOUT_MS: u64 = 2000;
+
+/// Options accepted by a DNS test, written as 'key=value' pairs after the
+/// domain (for example 'dns example.org type=MX server=10.0.0.53 expect=mx.example.org timeout=1s').
+struct DnsOptions {
+    record_type: RecordType,
+    nameserver: Option<SocketAddr>,
+    expected: Vec<String>,
+    warn_latency_ms: Option<u64>,
+    fail_latency_ms: Option<u64>,
+    timeout_ms: u64
+}
 
 pub struct DnsTest {}
 
@@ -11,15 +36,355 @@ impl DnsTest {
         DnsTest {}
     }
 
-    pub fn matches(&self, test: &str) -> bool {
+}
+
+#[async_trait]
+impl Test for DnsTest {
+
+    fn matches(&self, test: &str) -> bool {
 
-        test.starts_with("dns")
+        matches_type(test, "dns")
     }
 
-    pub async fn execute(&self, _test: &str) -> Result<TestResult, Error> {
+    /// Validate a DNS test command without querying any nameserver
+    fn parse(&self, test: &str) -> Result<(), Error> {
+
+        parse_test(test, &ThresholdConfig::default()).map(|_| ())
+    }
+
+    async fn execute(&self, test: &str, thresholds: &ThresholdConfig) -> Result<TestResult, Error> {
+
+        let (domain, options) = parse_test(test, thresholds)?;
+        let domain = domain.as_str();
+
+        let resolver = build_resolver(&options)?;
+
+        let latency_chrono = Instant::now();
+        let lookup_result = resolver.lookup(domain, options.record_type).await;
+        let duration = latency_chrono.elapsed();
+
+        let lookup = match lookup_result {
+            Ok(lookup) => lookup,
+            Err(err) => {
+
+                // The resolver reports empty answers (NODATA) as an error, the domain
+                // exists but has no record of the requested type
+                if let ResolveErrorKind::NoRecordsFound { response_code: ResponseCode::NoError, .. } = err.kind() {
+                    return Ok(no_records_result(domain, options.record_type));
+                }
+
+                // Unknown domains and unreachable nameservers: the DNS resolution
+                // did not work for the group
+                return Ok(TestResult::fail(domain).with_reason(FailureReason::DnsResolution).with_message(err.to_string()));
+            }
+        };
+
+        let answers: Vec<String> = lookup.iter().filter_map(format_record).collect();
+
+        let has_expected_answers = options.expected.iter().all(|expected_answer| {
+            answers.iter().any(|answer| answer.eq_ignore_ascii_case(expected_answer))
+        });
+
+        // Answers of unsupported record types are not listed
+        if answers.is_empty() {
+            return Ok(no_records_result(domain, options.record_type));
+        }
+
+        if !has_expected_answers {
+            let message = format!("expected {}, found {}", options.expected.join(", "), answers.join(", "));
+            return Ok(TestResult::fail(domain).with_reason(FailureReason::UnexpectedAnswer).with_message(message));
+        }
+
+        let duration_ms: f32 = duration.as_millis() as f32;
+
+        let metrics: HashMap<String, f32> = HashMap::from([
+            ("dns_latency".to_string(), duration_ms)
+        ]);
+
+        let category = latency_category(duration_ms, options.warn_latency_ms, options.fail_latency_ms);
+
+        let result = TestResult::build(domain, category, Some(metrics));
+        match category {
+            ResultCategory::Success => Ok(result),
+            _ => Ok(result.with_reason(FailureReason::Latency { latency_ms: duration_ms }))
+        }
+    }
+
+}
+
+fn parse_test(test: &str, thresholds: &ThresholdConfig) -> Result<(String, DnsOptions), Error> {
+
+    let dns_components = split_components(test);
+
+    let domain = dns_components.get(1)
+        .ok_or(Error::new("DNS test failed", "The DNS command expects a domain"))?;
+
+    let options = parse_options(&dns_components[2..], thresholds)?;
+
+    Ok((domain.to_string(), options))
+}
+
+fn parse_options(option_components: &[String], thresholds: &ThresholdConfig) -> Result<DnsOptions, Error> {
+
+    let mut options = DnsOptions {
+        record_type: RecordType::A,
+        nameserver: None,
+        expected: vec![],
+        warn_latency_ms: thresholds.warn_latency_ms,
+        fail_latency_ms: thresholds.fail_latency_ms,
+        timeout_ms: DEFAULT_DNS_TIMEOUT_MS
+    };
+
+    for option in option_components {
+
+        let (key, value) = option.split_once('=')
+            .ok_or_else(|| Error::new("DNS test failed", format!("Expected a 'key=value' option, found '{}'", option)))?;
+
+        let parse_milliseconds = |value: &str| parse_to_milliseconds(value)
+            .map_err(|err| Error::new("DNS test failed", format!("Option '{}' has an {}", key, err)));
+
+        match key {
+            "type" => {
+                options.record_type = parse_record_type(value)?;
+            },
+            "server" => {
+                options.nameserver = Some(parse_nameserver(value)?);
+            },
+            "expect" => {
+                options.expected = value.split(',')
+                    .map(|answer| answer.trim().trim_end_matches('.').to_string())
+                    .filter(|answer| !answer.is_empty())
+                    .collect();
+            },
+            "warn_latency" => options.warn_latency_ms = Some(parse_milliseconds(value)?),
+            "fail_latency" => options.fail_latency_ms = Some(parse_milliseconds(value)?),
+            "timeout" => options.timeout_ms = parse_milliseconds(value)?,
+            _ => {
+                return Err(Error::new("DNS test failed", format!("Unknown DNS option '{}'", key)));
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_record_type(record_type: &str) -> Result<RecordType, Error> {
+
+    match record_type.to_uppercase().as_str() {
+        "A" => Ok(RecordType::A),
+        "AAAA" => Ok(RecordType::AAAA),
+        "CNAME" => Ok(RecordType::CNAME),
+        "MX" => Ok(RecordType::MX),
+        "TXT" => Ok(RecordType::TXT),
+        "NS" => Ok(RecordType::NS),
+        _ => Err(Error::new("DNS test failed", format!("Unsupported DNS record type '{}'", record_type)))
+    }
+}
+
+fn parse_nameserver(nameserver: &str) -> Result<SocketAddr, Error> {
+
+    if let Ok(socket_addr) = nameserver.parse::<SocketAddr>() {
+        return Ok(socket_addr);
+    }
+
+    nameserver.parse::<IpAddr>()
+        .map(|ip_addr| SocketAddr::new(ip_addr, DEFAULT_DNS_PORT))
+        .map_err(|_| Error::new("DNS test failed", format!("Invalid nameserver address '{}'", nameserver)))
+}
+
+fn build_resolver(options: &DnsOptions) -> Result<TokioAsyncResolver, Error> {
+
+    let (config, mut resolver_opts) = match options.nameserver {
+        Some(nameserver) => {
+            let nameservers = NameServerConfigGroup::from_ips_clear(&[nameserver.ip()], nameserver.port(), true);
+            (ResolverConfig::from_parts(None, vec![], nameservers), ResolverOpts::default())
+        },
+        None => read_system_conf().map_err(|err| Error::new("Could not read system DNS configuration", err))?
+    };
+
+    // Each test should query the nameserver: caching answers in the relay
+    // would hide failures and report a meaningless latency
+    resolver_opts.cache_size = 0;
+    resolver_opts.attempts = 1;
+    resolver_opts.timeout = Duration::from_millis(options.timeout_ms);
+
+    TokioAsyncResolver::tokio(config, resolver_opts).map_err(|err| Error::new("Could not build DNS resolver", err))
+}
+
+fn no_records_result(domain: &str, record_type: RecordType) -> TestResult {
+
+    let message = format!("no {} records returned", record_type);
+    TestResult::fail(domain).with_reason(FailureReason::UnexpectedAnswer).with_message(message)
+}
+
+fn format_record(record: &RData) -> Option<String> {
+
+    let answer = match record {
+        RData::A(ip) => ip.to_string(),
+        RData::AAAA(ip) => ip.to_string(),
+        RData::CNAME(name) | RData::NS(name) => name.to_utf8(),
+        RData::MX(mx) => mx.exchange().to_utf8(),
+        RData::TXT(txt) => txt.txt_data().iter().map(|data| String::from_utf8_lossy(data).to_string()).collect(),
+        _ => return None
+    };
+
+    Some(answer.trim_end_matches('.').to_string())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::net::Ipv4Addr;
+    use std::str::FromStr;
+
+    use tokio::net::UdpSocket;
+    use trust_dns_resolver::proto::op::{Message, MessageType, ResponseCode};
+    use trust_dns_resolver::proto::rr::{Name, Record, rdata::{MX, TXT}};
+
+    use super::*;
+
+    /// Launch a local stub DNS server answering a fixed set of records for
+    /// 'example.org' (and NXDOMAIN for any other name)
+    async fn launch_stub_server() -> SocketAddr {
+
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let server_addr = socket.local_addr().unwrap();
+
+        tokio::spawn(async move {
+
+            let mut buffer = [0u8; 512];
+            loop {
+
+                let (length, peer) = match socket.recv_from(&mut buffer).await {
+                    Ok(received) => received,
+                    Err(_) => return
+                };
+
+                let request = Message::from_vec(&buffer[..length]).unwrap();
+                let query = request.queries()[0].clone();
+
+                let mut response = Message::new();
+                response.set_id(request.id())
+                    .set_message_type(MessageType::Response)
+                    .set_recursion_desired(true)
+                    .set_recursion_available(true)
+                    .add_query(query.clone());
+
+                let name = Name::from_str("example.org.").unwrap();
+                if query.name() != &name {
+                    response.set_response_code(ResponseCode::NXDomain);
+                } else {
+
+                    let rdata = match query.query_type() {
+                        RecordType::A => Some(RData::A(Ipv4Addr::new(10, 0, 0, 1))),
+                        RecordType::MX => Some(RData::MX(MX::new(10, Name::from_str("mail.example.org.").unwrap()))),
+                        RecordType::TXT => Some(RData::TXT(TXT::new(vec!["site-verification=watchdog".to_string()]))),
+                        _ => None
+                    };
+
+                    if let Some(rdata) = rdata {
+                        response.add_answer(Record::from_rdata(name, 60, rdata));
+                    }
+                }
+
+                socket.send_to(&response.to_vec().unwrap(), peer).await.unwrap();
+            }
+        });
+
+        server_addr
+    }
+
+    #[tokio::test]
+    async fn should_resolve_a_record() {
+
+        let server_addr = launch_stub_server().await;
+
+        let test = format!("dns example.org server={}", server_addr);
+        let result = DnsTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.target, "example.org");
+        assert_eq!(result.result, ResultCategory::Success);
+        assert!(result.metrics.unwrap().contains_key("dns_latency"));
+    }
+
+    #[tokio::test]
+    async fn should_match_expected_answers() {
+
+        let server_addr = launch_stub_server().await;
+
+        let test = format!("dns example.org type=MX server={} expect=mail.example.org", server_addr);
+        let result = DnsTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Success);
+
+        let test = format!("dns example.org type=txt server={} expect=site-verification=watchdog", server_addr);
+        let result = DnsTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Success);
+    }
+
+    #[tokio::test]
+    async fn should_apply_latency_thresholds() {
+
+        let server_addr = launch_stub_server().await;
+        let test = format!("dns example.org server={}", server_addr);
+
+        let thresholds = ThresholdConfig {
+            fail_latency_ms: Some(0),
+            ..ThresholdConfig::default()
+        };
+        let result = DnsTest::new().execute(&test, &thresholds).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Fail);
+
+        let result = DnsTest::new().execute(&format!("{} fail_latency=10s warn_latency=0ms", test), &thresholds).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Warning);
+    }
+
+    #[tokio::test]
+    async fn should_fail_unexpected_answer() {
+
+        let server_addr = launch_stub_server().await;
+
+        let test = format!("dns example.org server={} expect=10.0.0.2", server_addr);
+        let result = DnsTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::UnexpectedAnswer));
+    }
+
+    #[tokio::test]
+    async fn should_fail_unknown_domain() {
+
+        let server_addr = launch_stub_server().await;
+
+        let test = format!("dns unknown.example.org server={}", server_addr);
+        let result = DnsTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::DnsResolution));
+    }
+
+    #[tokio::test]
+    async fn should_fail_empty_answer() {
+
+        let server_addr = launch_stub_server().await;
+
+        let test = format!("dns example.org type=AAAA server={}", server_addr);
+        let result = DnsTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::UnexpectedAnswer));
+        assert_eq!(result.message.as_deref(), Some("no AAAA records returned"));
+    }
+
+    #[tokio::test]
+    async fn should_deny_invalid_options() {
+
+        let dns = DnsTest::new();
 
-        let error_message = Error::new("DNS test failed", "The 'dns' command is not supported yet"); 
-        Err(error_message)
+        assert!(dns.execute("dns", &ThresholdConfig::default()).await.is_err());
+        assert!(dns.execute("dns example.org type=SRV", &ThresholdConfig::default()).await.is_err());
+        assert!(dns.execute("dns example.org server=not-an-ip", &ThresholdConfig::default()).await.is_err());
+        assert!(dns.execute("dns example.org unknown=value", &ThresholdConfig::default()).await.is_err());
+        assert!(dns.execute("dns example.org timeout=soon", &ThresholdConfig::default()).await.is_err());
     }
 
 }
@@ -1,9 +1,32 @@
-use std::{str, collections::HashMap};
+use std::{str, collections::HashMap, error::Error as StdError, io, time::Duration};
 use tokio::time::Instant;
 
-use reqwest::Client;
+use reqwest::{Client, Method, header::{HeaderName, HeaderValue}};
+use async_trait::async_trait;
+use tokio_rustls::rustls;
 
-use crate::{common::error::Error, relay::model::{TestResult, ResultCategory}};
+use crate::{common::error::Error, relay::model::{FailureReason, TestResult, ResultCategory}, server::config::{parse_to_milliseconds, ThresholdConfig}};
+
+use super::{runner::Test, body::BodyAssertion, utils::{connect_failure, format_error_chain, latency_category, matches_type, split_components}};
+
+const DEFAULT_HTTP_TIMEOUT_MS: u64 = 10000;
+
+/// Options accepted by a HTTP test, written as 'key=value' pairs after the URL (for
+/// example 'http https://example.org/health method=POST header="X-Token: abc" status=200-299').
+///
+/// An unexpected status fails the test by default, while a 4xx or 5xx status only
+/// warns when the test has no expected status: 'status_mismatch' overrides both.
+struct HttpOptions {
+    method: Method,
+    headers: Vec<(HeaderName, HeaderValue)>,
+    expected_status: Option<(u16, u16)>,
+    status_mismatch: Option<ResultCategory>,
+    body_assertions: Vec<BodyAssertion>,
+    body_mismatch: ResultCategory,
+    warn_latency_ms: Option<u64>,
+    fail_latency_ms: Option<u64>,
+    timeout_ms: u64
+}
 
 pub struct HttpTest {
     client: Client
@@ -18,60 +41,511 @@ impl HttpTest {
         }
     }
 
-    pub fn matches(&self, test: &str) -> bool {
+}
+
+#[async_trait]
+impl Test for HttpTest {
+
+    fn matches(&self, test: &str) -> bool {
+
+        matches_type(test, "http")
+    }
+
+    /// Validate a HTTP test command without sending any request
+    fn parse(&self, test: &str) -> Result<(), Error> {
 
-        test.starts_with("http")
+        parse_test(test, &ThresholdConfig::default()).map(|_| ())
     }
 
-    pub async fn execute(&self, test: &str) -> Result<TestResult, Error> {
+    async fn execute(&self, test: &str, thresholds: &ThresholdConfig) -> Result<TestResult, Error> {
 
-        let result: Vec<String> = test.split(' ').map(|item| item.to_string()).collect();
-    
-        return match result.get(1) {
-            Some(domain) => {
+        let (target, options) = parse_test(test, thresholds)?;
+        let target = target.as_str();
 
-                let url = format!("http://{}", domain);
-                let builder = self.client.get(url)
-                    .header("user-agent", "watchdog-relay")
-                    .header("cache-control", "no-store");
+        // Targets without scheme (such as 'kongbytes.io/page') are requested over HTTP
+        let url = if target.starts_with("http://") || target.starts_with("https://") {
+            target.to_string()
+        } else {
+            format!("http://{}", target)
+        };
 
-                // Measure the time between the request sent out time and the first byte
-                // received time (not 100% accurate - but still reasonable workaround)
-                let latency_chrono = Instant::now();
-                let request_result = builder.send().await;
-                let duration = latency_chrono.elapsed();
+        let mut builder = self.client.request(options.method, url)
+            .header("user-agent", "watchdog-relay")
+            .header("cache-control", "no-store")
+            .timeout(Duration::from_millis(options.timeout_ms));
 
-                match request_result {
-                    Ok(response) => {
+        for (header_name, header_value) in options.headers {
+            builder = builder.header(header_name, header_value);
+        }
 
-                        let http_status = &response.status();
+        // Measure the time between the request sent out time and the first byte
+        // received time (not 100% accurate - but still reasonable workaround)
+        let latency_chrono = Instant::now();
+        let request_result = builder.send().await;
+        let duration = latency_chrono.elapsed();
 
-                        let category = if http_status.is_client_error() || http_status.is_server_error() {
-                            ResultCategory::Warning
+        match request_result {
+            Ok(response) => {
+
+                let http_status = response.status();
+
+                let duration_ms: f32 = duration.as_millis() as f32;
+
+                let metrics: HashMap<String, f32> = HashMap::from([
+                    ("http_latency".to_string(), duration_ms)
+                ]);
+
+                let status_code = http_status.as_u16();
+                let status_category = match options.expected_status {
+                    Some((min_status, max_status)) => {
+
+                        if status_code < min_status || status_code > max_status {
+
+                            let category = options.status_mismatch.unwrap_or(ResultCategory::Fail);
+                            let message = format!("expected HTTP status {}, found {}", format_status_range(min_status, max_status), status_code);
+                            let result = TestResult::build(target, category, Some(metrics)).with_message(message);
+                            return Ok(result.with_reason(FailureReason::HttpStatus { status: status_code }));
+                        }
+
+                        ResultCategory::Success
+                    },
+                    None => {
+
+                        if http_status.is_client_error() || http_status.is_server_error() {
+                            options.status_mismatch.unwrap_or(ResultCategory::Warning)
                         } else {
                             ResultCategory::Success
-                        };
+                        }
+                    }
+                };
 
-                        let duration_ms: f32 = duration.as_millis() as f32;
+                // The reason of a degraded test is the check with the worst category
+                let latency_category = latency_category(duration_ms, options.warn_latency_ms, options.fail_latency_ms);
+                let (category, reason) = if latency_category > status_category {
+                    (latency_category, Some(FailureReason::Latency { latency_ms: duration_ms }))
+                } else if status_category != ResultCategory::Success {
+                    (status_category, Some(FailureReason::HttpStatus { status: status_code }))
+                } else {
+                    (ResultCategory::Success, None)
+                };
 
-                        let metrics: HashMap<String, f32> = HashMap::from([
-                            ("http_latency".to_string(), duration_ms)
-                        ]);
+                // The body is only downloaded when the test has assertions on it, a
+                // mismatch is reported with the category configured in the test
+                if !options.body_assertions.is_empty() {
 
-                        return Ok(TestResult::build(domain, category, Some(metrics)));
+                    let body_mismatch = match response.text().await {
+                        Ok(body) => options.body_assertions.iter().find_map(|assertion| assertion.check(&body).err()),
+                        Err(_err) => Some("could not read response body".to_string())
+                    };
 
-                    },
-                    Err(_err) => {
-                        // TODO Error lost (DNS failure, ...)
-                        Ok(TestResult::fail(domain))
+                    if let Some(mismatch) = body_mismatch {
+
+                        let reason = if options.body_mismatch >= category {
+                            Some(FailureReason::BodyMismatch)
+                        } else {
+                            reason
+                        };
+                        let category = category.max(options.body_mismatch);
+                        return Ok(TestResult { reason, ..TestResult::build(target, category, Some(metrics)).with_message(mismatch) });
                     }
                 }
+
+                Ok(TestResult { reason, ..TestResult::build(target, category, Some(metrics)) })
+
             },
-            None => {
-                let error_message = Error::new("HTTP test failed", "The HTTP command expects a target"); 
-                Err(error_message)
+            Err(err) => {
+                Ok(TestResult::fail(target).with_reason(request_failure(&err)).with_message(format_error_chain(&err)))
+            }
+        }
+    }
+
+}
+
+/**
+ * Find the failure reason of a HTTP request by walking the error sources. Reqwest
+ * wraps the underlying errors (hyper connection errors, IO and TLS errors) and only
+ * exposes the timeout and connection flags.
+ */
+fn request_failure(err: &reqwest::Error) -> FailureReason {
+
+    if err.is_timeout() {
+        return FailureReason::Timeout;
+    }
+
+    let mut source = err.source();
+    while let Some(cause) = source {
+
+        if cause.is::<rustls::Error>() {
+            return FailureReason::Tls;
+        }
+
+        if let Some(io_err) = cause.downcast_ref::<io::Error>() {
+
+            // IO errors do not expose their inner error as a source
+            if io_err.get_ref().map(|inner| inner.is::<rustls::Error>()).unwrap_or(false) {
+                return FailureReason::Tls;
+            }
+            if io_err.kind() != io::ErrorKind::Other {
+                return connect_failure(io_err);
             }
+        }
+
+        // Hyper reports resolution failures as 'dns error' connection errors
+        if cause.to_string().starts_with("dns error") {
+            return FailureReason::DnsResolution;
+        }
+
+        source = cause.source();
+    }
+
+    FailureReason::Unreachable
+}
+
+fn parse_test(test: &str, thresholds: &ThresholdConfig) -> Result<(String, HttpOptions), Error> {
+
+    let http_components = split_components(test);
+
+    let target = http_components.get(1)
+        .ok_or(Error::new("HTTP test failed", "The HTTP command expects a target"))?;
+
+    let options = parse_options(&http_components[2..], thresholds)?;
+
+    Ok((target.to_string(), options))
+}
+
+fn parse_options(option_components: &[String], thresholds: &ThresholdConfig) -> Result<HttpOptions, Error> {
+
+    let mut options = HttpOptions {
+        method: Method::GET,
+        headers: vec![],
+        expected_status: None,
+        status_mismatch: None,
+        body_assertions: vec![],
+        body_mismatch: ResultCategory::Fail,
+        warn_latency_ms: thresholds.warn_latency_ms,
+        fail_latency_ms: thresholds.fail_latency_ms,
+        timeout_ms: DEFAULT_HTTP_TIMEOUT_MS
+    };
+
+    for option in option_components {
+
+        let (key, value) = option.split_once('=')
+            .ok_or_else(|| Error::new("HTTP test failed", format!("Expected a 'key=value' option, found '{}'", option)))?;
+
+        let parse_milliseconds = |value: &str| parse_to_milliseconds(value)
+            .map_err(|err| Error::new("HTTP test failed", format!("Option '{}' has an {}", key, err)));
+
+        let parse_mismatch_category = |value: &str| match value {
+            "warn" | "warning" => Ok(ResultCategory::Warning),
+            "fail" => Ok(ResultCategory::Fail),
+            _ => Err(Error::new("HTTP test failed", format!("Option '{}' expects 'warn' or 'fail', found '{}'", key, value)))
         };
+
+        match key {
+            "method" => {
+                options.method = Method::from_bytes(value.to_uppercase().as_bytes())
+                    .map_err(|_| Error::new("HTTP test failed", format!("Invalid HTTP method '{}'", value)))?;
+            },
+            "header" => {
+                options.headers.push(parse_header(value)?);
+            },
+            "status" => {
+                options.expected_status = Some(parse_status_range(value)?);
+            },
+            "status_mismatch" => {
+                options.status_mismatch = Some(parse_mismatch_category(value)?);
+            },
+            "body_contains" => {
+                options.body_assertions.push(BodyAssertion::contains(value));
+            },
+            "body_regex" => {
+                options.body_assertions.push(BodyAssertion::regex(value)?);
+            },
+            "body_json" => {
+                options.body_assertions.push(BodyAssertion::json_path(value)?);
+            },
+            "body_mismatch" => {
+                options.body_mismatch = parse_mismatch_category(value)?;
+            },
+            "warn_latency" => {
+                options.warn_latency_ms = Some(parse_milliseconds(value)?);
+            },
+            "fail_latency" => {
+                options.fail_latency_ms = Some(parse_milliseconds(value)?);
+            },
+            "timeout" => {
+                options.timeout_ms = parse_milliseconds(value)?;
+            },
+            _ => {
+                return Err(Error::new("HTTP test failed", format!("Unknown HTTP option '{}'", key)));
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+fn parse_header(header: &str) -> Result<(HeaderName, HeaderValue), Error> {
+
+    let invalid_header = || Error::new("HTTP test failed", format!("Expected a 'Name: value' header, found '{}'", header));
+
+    let (name, value) = header.split_once(':').ok_or_else(invalid_header)?;
+
+    let header_name = HeaderName::from_bytes(name.trim().as_bytes()).map_err(|_| invalid_header())?;
+    let header_value = HeaderValue::from_str(value.trim()).map_err(|_| invalid_header())?;
+
+    Ok((header_name, header_value))
+}
+
+/**
+ * Parse an expected HTTP status into an inclusive range. The status can be
+ * a single code ('200'), a range ('200-299') or a class of codes ('2xx').
+ */
+fn parse_status_range(status: &str) -> Result<(u16, u16), Error> {
+
+    let invalid_status = || Error::new("HTTP test failed", format!("Invalid expected HTTP status '{}'", status));
+    let parse_code = |code: &str| code.trim().parse::<u16>().ok().filter(|code| (100..=599).contains(code));
+
+    let lowercase_status = status.to_lowercase();
+    if let Some(status_class) = lowercase_status.strip_suffix("xx") {
+        let class = status_class.parse::<u16>().ok().filter(|class| (1..=5).contains(class)).ok_or_else(invalid_status)?;
+        return Ok((class * 100, class * 100 + 99));
+    }
+
+    let (min_status, max_status) = match status.split_once('-') {
+        Some((min_status, max_status)) => (parse_code(min_status), parse_code(max_status)),
+        None => (parse_code(status), parse_code(status))
+    };
+
+    match (min_status, max_status) {
+        (Some(min_status), Some(max_status)) if min_status <= max_status => Ok((min_status, max_status)),
+        _ => Err(invalid_status())
+    }
+}
+
+fn format_status_range(min_status: u16, max_status: u16) -> String {
+
+    if min_status == max_status {
+        min_status.to_string()
+    } else {
+        format!("{}-{}", min_status, max_status)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+
+    use std::net::TcpListener;
+
+    use axum::{Router, http::{HeaderMap, StatusCode}, routing::get};
+
+    use super::*;
+
+    /// Launch a local HTTP server and return its 'host:port' address
+    async fn launch_http_server() -> String {
+
+        let app = Router::new()
+            .route("/health", get(|| async { "ok" }).post(|| async { (StatusCode::CREATED, "created") }))
+            .route("/secured", get(|headers: HeaderMap| async move {
+                match headers.get("x-token").map(|token| token.as_bytes()) {
+                    Some(b"secret value") => StatusCode::OK,
+                    _ => StatusCode::UNAUTHORIZED
+                }
+            }))
+            .route("/status", get(|| async { axum::Json(serde_json::json!({ "status": "ok", "db": "down" })) }))
+            .route("/slow", get(|| async {
+                tokio::time::sleep(Duration::from_millis(500)).await;
+                "slow"
+            }));
+
+        let listener = TcpListener::bind("127.0.0.1:0").unwrap();
+        let address = listener.local_addr().unwrap().to_string();
+
+        let server = axum::Server::from_tcp(listener).unwrap().serve(app.into_make_service());
+        tokio::spawn(server);
+
+        address
+    }
+
+    #[tokio::test]
+    async fn should_request_full_url() {
+
+        let address = launch_http_server().await;
+
+        let target = format!("http://{}/health", address);
+        let result = HttpTest::new().execute(&format!("http {} status=200", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.target, target);
+        assert_eq!(result.result, ResultCategory::Success);
+        assert!(result.metrics.unwrap().contains_key("http_latency"));
+    }
+
+    #[tokio::test]
+    async fn should_request_with_method() {
+
+        let address = launch_http_server().await;
+
+        let test = format!("http {}/health method=post status=201", address);
+        let result = HttpTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Success);
+    }
+
+    #[tokio::test]
+    async fn should_request_with_headers() {
+
+        let address = launch_http_server().await;
+
+        let test = format!("http {}/secured header=\"X-Token: secret value\" status=2xx", address);
+        let result = HttpTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Success);
+    }
+
+    #[tokio::test]
+    async fn should_fail_unexpected_status() {
+
+        let address = launch_http_server().await;
+
+        let test = format!("http {}/secured status=200-299", address);
+        let result = HttpTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.message, Some("expected HTTP status 200-299, found 401".to_string()));
+    }
+
+    #[tokio::test]
+    async fn should_warn_error_status_without_expectation() {
+
+        let address = launch_http_server().await;
+
+        let result = HttpTest::new().execute(&format!("http {}/unknown", address), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Warning);
+    }
+
+    #[tokio::test]
+    async fn should_apply_status_mismatch_category() {
+
+        let address = launch_http_server().await;
+
+        let test = format!("http {}/secured status=200 status_mismatch=warn", address);
+        let result = HttpTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Warning);
+
+        let test = format!("http {}/unknown status_mismatch=fail", address);
+        let result = HttpTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Fail);
+    }
+
+    #[tokio::test]
+    async fn should_apply_latency_thresholds() {
+
+        let address = launch_http_server().await;
+        let test = format!("http {}/slow", address);
+
+        let thresholds = ThresholdConfig {
+            warn_latency_ms: Some(100),
+            ..ThresholdConfig::default()
+        };
+        let result = HttpTest::new().execute(&test, &thresholds).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Warning);
+
+        // Options written in the test take precedence over the group thresholds
+        let result = HttpTest::new().execute(&format!("{} fail_latency=200ms", test), &thresholds).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Fail);
+
+        let result = HttpTest::new().execute(&format!("{} warn_latency=10s", test), &thresholds).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Success);
+    }
+
+    #[tokio::test]
+    async fn should_fail_on_timeout() {
+
+        let address = launch_http_server().await;
+
+        let target = format!("{}/slow", address);
+        let result = HttpTest::new().execute(&format!("http {} timeout=100ms", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::Timeout));
+    }
+
+    #[tokio::test]
+    async fn should_report_failure_reason() {
+
+        // The port is released before the request, the connection is refused
+        let address = TcpListener::bind("127.0.0.1:0").unwrap().local_addr().unwrap();
+        let result = HttpTest::new().execute(&format!("http {}", address), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::ConnectionRefused));
+
+        let address = launch_http_server().await;
+        let result = HttpTest::new().execute(&format!("http {}/secured status=200", address), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.reason, Some(FailureReason::HttpStatus { status: 401 }));
+    }
+
+    #[tokio::test]
+    async fn should_match_body_assertions() {
+
+        let address = launch_http_server().await;
+
+        let test = format!("http {}/status body_contains=status body_regex=\"d[a-z]+n\" body_json=\"$.status == 'ok'\"", address);
+        let result = HttpTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Success);
+        assert_eq!(result.message, None);
+    }
+
+    #[tokio::test]
+    async fn should_fail_body_mismatch() {
+
+        let address = launch_http_server().await;
+
+        let test = format!("http {}/status body_json=\"$.db == 'up'\"", address);
+        let result = HttpTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.message, Some("expected $.db == \"up\", found \"down\"".to_string()));
+    }
+
+    #[tokio::test]
+    async fn should_warn_body_mismatch() {
+
+        let address = launch_http_server().await;
+
+        let test = format!("http {}/status body_contains=healthy body_mismatch=warn", address);
+        let result = HttpTest::new().execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Warning);
+        assert_eq!(result.message, Some("body does not contain 'healthy'".to_string()));
+    }
+
+    #[test]
+    fn should_parse_status_range() {
+
+        assert_eq!(parse_status_range("200").unwrap(), (200, 200));
+        assert_eq!(parse_status_range("200-204").unwrap(), (200, 204));
+        assert_eq!(parse_status_range("3xx").unwrap(), (300, 399));
+        assert!(parse_status_range("299-200").is_err());
+        assert!(parse_status_range("9xx").is_err());
+        assert!(parse_status_range("ok").is_err());
+    }
+
+    #[tokio::test]
+    async fn should_deny_invalid_options() {
+
+        let http = HttpTest::new();
+
+        assert!(http.execute("http", &ThresholdConfig::default()).await.is_err());
+        assert!(http.execute("http example.org method=\"GE T\"", &ThresholdConfig::default()).await.is_err());
+        assert!(http.execute("http example.org header=invalid", &ThresholdConfig::default()).await.is_err());
+        assert!(http.execute("http example.org timeout=soon", &ThresholdConfig::default()).await.is_err());
+        assert!(http.execute("http example.org body_regex=\"(\"", &ThresholdConfig::default()).await.is_err());
+        assert!(http.execute("http example.org body_mismatch=ignore", &ThresholdConfig::default()).await.is_err());
+        assert!(http.execute("http example.org unknown=value", &ThresholdConfig::default()).await.is_err());
     }
 
 }
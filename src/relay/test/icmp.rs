@@ -0,0 +1,236 @@
+use std::{io, net::{IpAddr, SocketAddr}, process, sync::atomic::{AtomicU16, Ordering}, time::Duration};
+use tokio::{net::UdpSocket, time::{timeout_at, Instant}};
+
+use socket2::{Domain, Protocol, Socket, Type};
+
+use crate::common::error::Error;
+
+const ICMPV4_ECHO_REQUEST: u8 = 8;
+const ICMPV4_ECHO_REPLY: u8 = 0;
+const ICMPV6_ECHO_REQUEST: u8 = 128;
+const ICMPV6_ECHO_REPLY: u8 = 129;
+
+const ECHO_PAYLOAD: &[u8] = b"watchdog-relay-ping";
+
+// Each socket gets its own identifier, since raw sockets receive all ICMP
+// replies of the host (including replies for other pings)
+static NEXT_IDENTIFIER: AtomicU16 = AtomicU16::new(0);
+
+/// ICMP echo socket, using an unprivileged ICMP datagram socket when allowed by the
+/// system (see the 'net.ipv4.ping_group_range' sysctl on Linux) and a raw socket
+/// otherwise (root or CAP_NET_RAW).
+pub struct IcmpSocket {
+    socket: UdpSocket,
+    is_raw: bool,
+    is_ipv6: bool,
+    identifier: u16
+}
+
+impl IcmpSocket {
+
+    pub fn open(target: IpAddr) -> Result<Self, Error> {
+
+        let (domain, protocol) = match target {
+            IpAddr::V4(_) => (Domain::IPV4, Protocol::ICMPV4),
+            IpAddr::V6(_) => (Domain::IPV6, Protocol::ICMPV6)
+        };
+
+        let (socket, is_raw) = match Socket::new(domain, Type::DGRAM, Some(protocol)) {
+            Ok(socket) => (socket, false),
+            Err(datagram_err) => {
+                let socket = Socket::new(domain, Type::RAW, Some(protocol)).map_err(|raw_err| {
+                    let details = format!("datagram socket: {}, raw socket: {}", datagram_err, raw_err);
+                    Error::new("Could not open ICMP socket (check ping_group_range or CAP_NET_RAW)", details)
+                })?;
+                (socket, true)
+            }
+        };
+
+        // The ICMP socket is wrapped in a Tokio UDP socket, which only relies on
+        // the 'sendto' and 'recvfrom' system calls for a non-blocking socket
+        socket.set_nonblocking(true).map_err(|err| Error::new("Could not configure ICMP socket", err))?;
+        let socket = UdpSocket::from_std(socket.into()).map_err(|err| Error::new("Could not configure ICMP socket", err))?;
+
+        let identifier = (process::id() as u16).wrapping_add(NEXT_IDENTIFIER.fetch_add(1, Ordering::Relaxed));
+
+        Ok(IcmpSocket {
+            socket,
+            is_raw,
+            is_ipv6: target.is_ipv6(),
+            identifier
+        })
+    }
+
+    /// Send 'count' ICMP echo requests separated by an interval, replies are collected while
+    /// the next requests are sent. The round-trip time of each request is returned, or 'None'
+    /// if no reply has been received before the timeout (packet loss).
+    pub async fn ping(&self, target: IpAddr, count: u16, interval: Duration, reply_timeout: Duration) -> Vec<Option<Duration>> {
+
+        let target_addr = SocketAddr::new(target, 0);
+        let start = Instant::now();
+
+        let mut send_times: Vec<Option<Instant>> = vec![None; count as usize];
+        let mut round_trips: Vec<Option<Duration>> = vec![None; count as usize];
+        let mut next_sequence: u16 = 0;
+
+        let mut buffer = [0u8; 1500];
+        loop {
+
+            let wait_until = if next_sequence < count {
+
+                let send_at = start + interval * next_sequence as u32;
+                if Instant::now() >= send_at {
+
+                    // Send errors (network unreachable, no route to host, ...) are
+                    // handled as a packet loss
+                    let request = self.build_request(next_sequence);
+                    if self.socket.send_to(&request, target_addr).await.is_ok() {
+                        send_times[next_sequence as usize] = Some(Instant::now());
+                    }
+
+                    next_sequence += 1;
+                    continue;
+                }
+                send_at
+
+            } else {
+
+                if round_trips.iter().zip(&send_times).all(|(round_trip, send_time)| round_trip.is_some() || send_time.is_none()) {
+                    break;
+                }
+
+                let last_send = send_times.iter().flatten().max().cloned().unwrap_or(start);
+                last_send + reply_timeout
+            };
+
+            let received = match timeout_at(wait_until, self.socket.recv_from(&mut buffer)).await {
+                Ok(Ok(received)) => received,
+                Ok(Err(_)) => break,
+                Err(_) if next_sequence < count => continue,
+                Err(_) => break
+            };
+
+            let (length, source) = received;
+            if source.ip() != target {
+                continue;
+            }
+
+            if let Some(sequence) = self.parse_reply_sequence(&buffer[..length]) {
+
+                let send_time = send_times.get(sequence as usize).cloned().flatten();
+                if let Some(send_time) = send_time {
+
+                    let round_trip = send_time.elapsed();
+                    if round_trip <= reply_timeout && round_trips[sequence as usize].is_none() {
+                        round_trips[sequence as usize] = Some(round_trip);
+                    }
+                }
+            }
+        }
+
+        round_trips
+    }
+
+    fn build_request(&self, sequence: u16) -> Vec<u8> {
+
+        let echo_type = if self.is_ipv6 { ICMPV6_ECHO_REQUEST } else { ICMPV4_ECHO_REQUEST };
+
+        let mut packet = vec![echo_type, 0, 0, 0];
+        packet.extend_from_slice(&self.identifier.to_be_bytes());
+        packet.extend_from_slice(&sequence.to_be_bytes());
+        packet.extend_from_slice(ECHO_PAYLOAD);
+
+        // The ICMPv6 checksum includes a pseudo-header and is computed by the kernel
+        if !self.is_ipv6 {
+            let checksum = compute_checksum(&packet);
+            packet[2..4].copy_from_slice(&checksum.to_be_bytes());
+        }
+
+        packet
+    }
+
+    fn parse_reply_sequence(&self, packet: &[u8]) -> Option<u16> {
+
+        // Raw IPv4 sockets receive the IP header with the ICMP message
+        let icmp_packet = if self.is_raw && !self.is_ipv6 {
+            let header_length = packet.first().map(|version_ihl| ((version_ihl & 0x0f) as usize) * 4).unwrap_or(0);
+            packet.get(header_length..).unwrap_or_default()
+        } else {
+            packet
+        };
+
+        if icmp_packet.len() < 8 {
+            return None;
+        }
+
+        let reply_type = if self.is_ipv6 { ICMPV6_ECHO_REPLY } else { ICMPV4_ECHO_REPLY };
+        let identifier = u16::from_be_bytes([icmp_packet[4], icmp_packet[5]]);
+        let reply_sequence = u16::from_be_bytes([icmp_packet[6], icmp_packet[7]]);
+
+        // Datagram sockets have their identifier rewritten by the kernel, which also
+        // filters the replies delivered to the socket
+        if icmp_packet[0] == reply_type && (!self.is_raw || identifier == self.identifier) {
+            return Some(reply_sequence);
+        }
+
+        None
+    }
+
+}
+
+/// Resolve a ping target (an IP address or a hostname) to an IP address
+pub async fn resolve_target(target: &str) -> io::Result<IpAddr> {
+
+    if let Ok(ip_addr) = target.parse::<IpAddr>() {
+        return Ok(ip_addr);
+    }
+
+    tokio::net::lookup_host((target, 0)).await?
+        .next()
+        .map(|socket_addr| socket_addr.ip())
+        .ok_or_else(|| io::Error::new(io::ErrorKind::NotFound, "no address found"))
+}
+
+/// Internet checksum (RFC 1071) used by ICMPv4 messages
+fn compute_checksum(packet: &[u8]) -> u16 {
+
+    let mut sum: u32 = packet.chunks(2)
+        .map(|chunk| u16::from_be_bytes([chunk[0], *chunk.get(1).unwrap_or(&0)]) as u32)
+        .sum();
+
+    while sum >> 16 != 0 {
+        sum = (sum & 0xffff) + (sum >> 16);
+    }
+
+    !(sum as u16)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_compute_checksum() {
+
+        // Echo request with identifier 1 and sequence 1 (no payload)
+        let packet = [8, 0, 0, 0, 0, 1, 0, 1];
+        assert_eq!(compute_checksum(&packet), 0xf7fd);
+
+        let odd_packet = [8, 0, 0, 0, 0, 1, 0, 1, 0xff];
+        assert_eq!(compute_checksum(&odd_packet), 0xf8fc);
+    }
+
+    #[tokio::test]
+    async fn should_ping_loopback() {
+
+        let target: IpAddr = "127.0.0.1".parse().unwrap();
+        let socket = IcmpSocket::open(target).unwrap();
+
+        let round_trips = socket.ping(target, 3, Duration::from_millis(10), Duration::from_secs(1)).await;
+
+        assert_eq!(round_trips.len(), 3);
+        assert!(round_trips.iter().all(|round_trip| round_trip.is_some()));
+    }
+
+}
@@ -1,5 +1,10 @@
 pub mod runner;
 
 mod ping;
+mod icmp;
 mod http;
-mod dns;
\ No newline at end of file
+mod body;
+mod dns;
+mod tcp;
+mod tls;
+pub mod utils;
\ No newline at end of file
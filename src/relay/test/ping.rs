@@ -1,8 +1,34 @@
-use std::{str, collections::HashMap};
-
-use tokio::process::Command;
-
-use crate::{common::error::Error, relay::model::{TestResult, ResultCategory}};
+use std::{str, collections::HashMap, time::Duration};
+
+use async_trait::async_trait;
+
+use crate::{common::error::Error, relay::model::{FailureReason, TestResult, ResultCategory}, server::config::{parse_to_milliseconds, ThresholdConfig}};
+
+use super::{runner::Test, icmp::{IcmpSocket, resolve_target}, utils::{latency_category, matches_type, split_components}};
+
+const DEFAULT_PING_COUNT: u16 = 1;
+const MAX_PING_COUNT: u16 = 100;
+const DEFAULT_PING_INTERVAL_MS: u64 = 200;
+const DEFAULT_PING_TIMEOUT_MS: u64 = 2000;
+const DEFAULT_PING_WARN_MS: u64 = 100;
+
+/// Options accepted by a ping test, written as 'key=value' pairs after the target (for
+/// example 'ping 1.1.1.1 count=5 warn_loss=20 fail_loss=60 warn_latency=150ms').
+///
+/// Thresholds are inclusive: the test warns when the packet loss (in %) reaches
+/// 'warn_loss' (any loss by default) or when the average round-trip time reaches
+/// 'warn_latency', and fails when 'fail_loss' (all packets by default) or
+/// 'fail_latency' (disabled by default) are reached. The group thresholds
+/// replace these defaults, but not the options written in the test.
+struct PingOptions {
+    count: u16,
+    interval_ms: u64,
+    timeout_ms: u64,
+    warn_latency_ms: Option<u64>,
+    fail_latency_ms: Option<u64>,
+    warn_loss_pct: f32,
+    fail_loss_pct: f32
+}
 
 pub struct PingTest {}
 
@@ -13,70 +39,207 @@ impl PingTest {
         PingTest {}
     }
 
-    pub fn matches(&self, test: &str) -> bool {
+}
 
-        test.starts_with("ping")
+#[async_trait]
+impl Test for PingTest {
+
+    fn matches(&self, test: &str) -> bool {
+
+        matches_type(test, "ping")
     }
 
-    pub async fn execute(&self, test: &str) -> Result<TestResult, Error> {
+    /// Validate a ping test command without sending any packet
+    fn parse(&self, test: &str) -> Result<(), Error> {
 
-        let ping_components: Vec<&str> = test.split(' ').collect();
+        parse_test(test, &ThresholdConfig::default()).map(|_| ())
+    }
 
-        let target = ping_components.get(1)
-            .cloned()
-            .ok_or(Error::new("Ping test failed", "The ping command expects a valid target"))?;
+    async fn execute(&self, test: &str, thresholds: &ThresholdConfig) -> Result<TestResult, Error> {
 
-        let command_output = Command::new("/usr/bin/ping")
-            .arg("-c")
-            .arg("1")
-            .arg("-w")
-            .arg("2")
-            .arg(target)
-            .output()
-            .await;
+        let (target, options) = parse_test(test, thresholds)?;
+        let target = target.as_str();
 
-        let output = match command_output {
-            Ok(output) => output,
-            Err(err) => {
-                return Err(Error::new("Failed to ping", err));
-            }
+        let target_ip = match resolve_target(target).await {
+            Ok(target_ip) => target_ip,
+            Err(err) => return Ok(TestResult::fail(target).with_reason(FailureReason::DnsResolution).with_message(err.to_string()))
         };
-        
-        if !output.status.success() {
-            return Ok(TestResult::fail(target));
+
+        let socket = IcmpSocket::open(target_ip)?;
+        let round_trips = socket.ping(
+            target_ip,
+            options.count,
+            Duration::from_millis(options.interval_ms),
+            Duration::from_millis(options.timeout_ms)
+        ).await;
+
+        let rtts: Vec<f32> = round_trips.iter()
+            .flatten()
+            .map(|round_trip| round_trip.as_micros() as f32 / 1000.0)
+            .collect();
+
+        let loss_pct = 100.0 * (round_trips.len() - rtts.len()) as f32 / round_trips.len() as f32;
+
+        let mut metrics: HashMap<String, f32> = HashMap::from([
+            ("ping_loss_pct".to_string(), loss_pct)
+        ]);
+
+        if rtts.is_empty() {
+            let result = TestResult::build(target, ResultCategory::Fail, Some(metrics));
+            return Ok(result.with_reason(FailureReason::PacketLoss { loss_pct }));
         }
 
-        let stdout = match String::from_utf8(output.stdout) {
-            Ok(stdout) => stdout,
-            Err(err) => {
-                return Err(Error::new("Failed to ping", err));
-            }
+        let rtt_min = rtts.iter().cloned().fold(f32::INFINITY, f32::min);
+        let rtt_max = rtts.iter().cloned().fold(f32::NEG_INFINITY, f32::max);
+        let rtt_avg = rtts.iter().sum::<f32>() / rtts.len() as f32;
+
+        // Jitter is the mean deviation between consecutive round-trip times
+        let jitter = if rtts.len() > 1 {
+            rtts.windows(2).map(|pair| (pair[1] - pair[0]).abs()).sum::<f32>() / (rtts.len() - 1) as f32
+        } else {
+            0.0
+        };
+
+        // The 'ping_rtt' metric is kept as the average round-trip time for
+        // existing dashboards and Kuma updates
+        metrics.insert("ping_rtt".into(), rtt_avg);
+        metrics.insert("ping_rtt_min".into(), rtt_min);
+        metrics.insert("ping_rtt_avg".into(), rtt_avg);
+        metrics.insert("ping_rtt_max".into(), rtt_max);
+        metrics.insert("ping_jitter".into(), jitter);
+
+        let loss_category = if loss_pct >= options.fail_loss_pct {
+            ResultCategory::Fail
+        } else if loss_pct >= options.warn_loss_pct {
+            ResultCategory::Warning
+        } else {
+            ResultCategory::Success
         };
 
-        let rtt_result = stdout.lines()
-            .find(|s| s.starts_with("rtt"))
-            .unwrap_or_default()
-            .split(" = ")
-            .collect::<Vec<&str>>()
-            .get(1)
-            .map(|s| s.split('/').next())
-            .unwrap_or_default()
-            .unwrap_or_default()
-            .parse::<f32>();
+        // The reason of a degraded test is the check with the worst category
+        let latency_category = latency_category(rtt_avg, options.warn_latency_ms, options.fail_latency_ms);
+        let (category, reason) = if latency_category > loss_category {
+            (latency_category, Some(FailureReason::Latency { latency_ms: rtt_avg }))
+        } else if loss_category != ResultCategory::Success {
+            (loss_category, Some(FailureReason::PacketLoss { loss_pct }))
+        } else {
+            (ResultCategory::Success, None)
+        };
 
-        match rtt_result {
-            Ok(rtt) => {
+        Ok(TestResult { reason, ..TestResult::build(target, category, Some(metrics)) })
+    }
+
+}
+
+fn parse_test(test: &str, thresholds: &ThresholdConfig) -> Result<(String, PingOptions), Error> {
+
+    let ping_components = split_components(test);
+
+    let target = ping_components.get(1)
+        .ok_or(Error::new("Ping test failed", "The ping command expects a valid target"))?;
+
+    let options = parse_options(&ping_components[2..], thresholds)?;
+
+    Ok((target.to_string(), options))
+}
 
-                let mut metrics: HashMap<String, f32> = HashMap::new();
-                metrics.insert("ping_rtt".into(), rtt);
+fn parse_options(option_components: &[String], thresholds: &ThresholdConfig) -> Result<PingOptions, Error> {
 
-                let category = if rtt >= 100.0 { ResultCategory::Warning } else { ResultCategory::Success };
+    let mut options = PingOptions {
+        count: DEFAULT_PING_COUNT,
+        interval_ms: DEFAULT_PING_INTERVAL_MS,
+        timeout_ms: DEFAULT_PING_TIMEOUT_MS,
+        warn_latency_ms: thresholds.warn_latency_ms.or(Some(DEFAULT_PING_WARN_MS)),
+        fail_latency_ms: thresholds.fail_latency_ms,
+        warn_loss_pct: thresholds.warn_loss_pct.unwrap_or(f32::MIN_POSITIVE),
+        fail_loss_pct: thresholds.fail_loss_pct.unwrap_or(100.0)
+    };
 
-                Ok(TestResult::build(target, category, Some(metrics)))
+    for option in option_components {
 
+        let (key, value) = option.split_once('=')
+            .ok_or_else(|| Error::new("Ping test failed", format!("Expected a 'key=value' option, found '{}'", option)))?;
+
+        let parse_milliseconds = |value: &str| parse_to_milliseconds(value)
+            .map_err(|err| Error::new("Ping test failed", format!("Option '{}' has an {}", key, err)));
+
+        let parse_percentage = |value: &str| value.trim_end_matches('%').parse::<f32>().ok()
+            .filter(|percentage| (0.0..=100.0).contains(percentage))
+            .ok_or_else(|| Error::new("Ping test failed", format!("Option '{}' expects a percentage between 0 and 100, found '{}'", key, value)));
+
+        match key {
+            "count" => {
+                options.count = value.parse::<u16>().ok()
+                    .filter(|count| (1..=MAX_PING_COUNT).contains(count))
+                    .ok_or_else(|| Error::new("Ping test failed", format!("Option 'count' expects a number between 1 and {}, found '{}'", MAX_PING_COUNT, value)))?;
             },
-            Err(err) => Err(Error::new("Failed to ping", err)),
+            "interval" => options.interval_ms = parse_milliseconds(value)?,
+            "timeout" => options.timeout_ms = parse_milliseconds(value)?,
+            "warn_latency" => options.warn_latency_ms = Some(parse_milliseconds(value)?),
+            "fail_latency" => options.fail_latency_ms = Some(parse_milliseconds(value)?),
+            "warn_loss" => options.warn_loss_pct = parse_percentage(value)?,
+            "fail_loss" => options.fail_loss_pct = parse_percentage(value)?,
+            _ => {
+                return Err(Error::new("Ping test failed", format!("Unknown ping option '{}'", key)));
+            }
         }
     }
 
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_report_packet_statistics() {
+
+        let result = PingTest::new().execute("ping 127.0.0.1 count=3 interval=10ms", &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Success);
+
+        let metrics = result.metrics.unwrap();
+        assert_eq!(metrics.get("ping_loss_pct"), Some(&0.0));
+        assert!(metrics.get("ping_rtt_min").unwrap() <= metrics.get("ping_rtt_avg").unwrap());
+        assert!(metrics.get("ping_rtt_avg").unwrap() <= metrics.get("ping_rtt_max").unwrap());
+        assert!(metrics.contains_key("ping_jitter"));
+        assert!(metrics.contains_key("ping_rtt"));
+    }
+
+    #[tokio::test]
+    async fn should_apply_latency_thresholds() {
+
+        let ping = PingTest::new();
+
+        let result = ping.execute("ping 127.0.0.1 warn_latency=0ms", &ThresholdConfig::default()).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Warning);
+
+        let result = ping.execute("ping 127.0.0.1 fail_latency=0ms", &ThresholdConfig::default()).await.unwrap();
+        assert_eq!(result.result, ResultCategory::Fail);
+    }
+
+    #[tokio::test]
+    async fn should_fail_on_packet_loss() {
+
+        let result = PingTest::new().execute("ping 10.99.99.99 count=2 interval=10ms timeout=200ms", &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.metrics.unwrap().get("ping_loss_pct"), Some(&100.0));
+    }
+
+    #[tokio::test]
+    async fn should_deny_invalid_options() {
+
+        let ping = PingTest::new();
+
+        assert!(ping.execute("ping", &ThresholdConfig::default()).await.is_err());
+        assert!(ping.execute("ping 127.0.0.1 count=0", &ThresholdConfig::default()).await.is_err());
+        assert!(ping.execute("ping 127.0.0.1 count=1000", &ThresholdConfig::default()).await.is_err());
+        assert!(ping.execute("ping 127.0.0.1 warn_loss=120", &ThresholdConfig::default()).await.is_err());
+        assert!(ping.execute("ping 127.0.0.1 interval=soon", &ThresholdConfig::default()).await.is_err());
+        assert!(ping.execute("ping 127.0.0.1 unknown=1", &ThresholdConfig::default()).await.is_err());
+    }
+
 }
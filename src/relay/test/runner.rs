@@ -1,50 +1,107 @@
 use std::str;
 
-use crate::{common::error::Error, relay::model::TestResult};
+use async_trait::async_trait;
 
-use super::{ping::PingTest, http::HttpTest, dns::DnsTest};
+use crate::{common::error::Error, relay::model::TestResult, server::config::ThresholdConfig};
+
+use super::{ping::PingTest, http::HttpTest, dns::DnsTest, tcp::TcpTest, tls::TlsTest};
+
+/// A type of network test (ping, http, ...) that can be executed by relays. Test
+/// commands are dispatched to the first registered test matching the command, see
+/// the built-in tests for examples.
+#[async_trait]
+pub trait Test: Send + Sync {
+
+    /// Check if the test command is handled by this test type, usually by
+    /// looking at the first component of the command (such as 'ping')
+    fn matches(&self, test: &str) -> bool;
+
+    /// Parse and validate a test command without executing it, the server
+    /// refuses to start when a test from the configuration is invalid
+    fn parse(&self, test: &str) -> Result<(), Error>;
+
+    /// Execute a test command, the group thresholds should be used as defaults
+    /// for the warning & failure levels not configured in the test itself
+    async fn execute(&self, test: &str, thresholds: &ThresholdConfig) -> Result<TestResult, Error>;
+
+}
 
 pub struct TestRunner {
-    ping: PingTest,
-    http: HttpTest,
-    dns: DnsTest
+    tests: Vec<Box<dyn Test>>
 }
 
 impl TestRunner {
 
+    /// Build a runner with all built-in tests (ping, dns, tcp, tls & http)
     pub fn new() -> Self {
 
+        let mut runner = TestRunner::empty();
+
+        runner.register(PingTest::new());
+        runner.register(DnsTest::new());
+        runner.register(TcpTest::new());
+        runner.register(TlsTest::new());
+        runner.register(HttpTest::new());
+
+        runner
+    }
+
+    /// Build a runner without any test, only the registered tests will be available
+    pub fn empty() -> Self {
+
         TestRunner {
-            ping: PingTest::new(),
-            http: HttpTest::new(),
-            dns: DnsTest::new()
+            tests: vec![]
         }
     }
 
-    pub async fn execute_test(&self, test: &str) -> Result<TestResult, Error> {
+    /// Register an additional test type, tests registered first take precedence
+    /// when several tests match the same command
+    pub fn register<T>(&mut self, test: T) where T: Test + 'static {
 
-        if self.ping.matches(test) {
-            return self.ping.execute(test).await;
-        }
-    
-        if self.dns.matches(test) {
-            return self.dns.execute(test).await;   
-        }
-    
-        if self.http.matches(test)  {
-            return self.http.execute(test).await;
+        self.tests.push(Box::new(test));
+    }
+
+    /// Parse and validate a test command without executing it, an error is
+    /// returned for unknown test types and invalid targets or options
+    pub fn parse_test(&self, test: &str) -> Result<(), Error> {
+
+        self.find_test(test)?.parse(test)
+    }
+
+    /// Execute a test command, the group thresholds are used as defaults for
+    /// the warning & failure levels not configured in the test itself
+    pub async fn execute_test(&self, test: &str, thresholds: &ThresholdConfig) -> Result<TestResult, Error> {
+
+        self.find_test(test)?.execute(test, thresholds).await
+    }
+
+    fn find_test(&self, test: &str) -> Result<&dyn Test, Error> {
+
+        match self.tests.iter().find(|registered_test| registered_test.matches(test)) {
+            Some(registered_test) => Ok(registered_test.as_ref()),
+            None => {
+                let error_message = format!("Test '{}' failed, command not found", test);
+                Err(Error::basic(error_message))
+            }
         }
-    
-        let error_message = format!("Test '{}' failed, command not found", test);
-        Err(Error::basic(error_message))
+    }
+
+}
+
+impl Default for TestRunner {
+
+    fn default() -> Self {
+
+        TestRunner::new()
     }
 
 }
 
 #[cfg(test)]
+#[allow(clippy::bool_assert_comparison)]
 mod tests {
 
-    use crate::relay::model::ResultCategory;
+    use crate::relay::{model::{FailureReason, ResultCategory}, test::utils::matches_type};
 
     use super::*;
 
@@ -52,7 +109,7 @@ mod tests {
     async fn should_request_http_domain() {
         
         let runner = TestRunner::new();
-        let test_result = runner.execute_test("http kongbytes.io").await;
+        let test_result = runner.execute_test("http kongbytes.io", &ThresholdConfig::default()).await;
 
         assert_eq!(test_result.is_ok(), true);
         let result = test_result.unwrap();
@@ -71,7 +128,7 @@ mod tests {
     async fn should_request_http_path() {
 
         let runner = TestRunner::new();
-        let test_result = runner.execute_test("http github.com/kongbytes").await;
+        let test_result = runner.execute_test("http github.com/kongbytes", &ThresholdConfig::default()).await;
 
         assert_eq!(test_result.is_ok(), true);
         let result = test_result.unwrap();
@@ -90,14 +147,17 @@ mod tests {
     async fn should_fail_http_invalid_domain() {
         
         let runner = TestRunner::new();
-        assert_eq!(runner.execute_test("http www.this-does-not-exist.be").await, Ok(TestResult::fail("www.this-does-not-exist.be")));
+        let result = runner.execute_test("http www.this-does-not-exist.be", &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::DnsResolution));
     }
 
     #[tokio::test]
     async fn should_fail_http_unknown_page() {
 
         let runner = TestRunner::new();
-        let test_result = runner.execute_test("http kongbytes.io/unknown.html").await;
+        let test_result = runner.execute_test("http kongbytes.io/unknown.html", &ThresholdConfig::default()).await;
 
         assert_eq!(test_result.is_ok(), true);
         let result = test_result.unwrap();
@@ -116,7 +176,7 @@ mod tests {
     async fn should_perform_valid_ping() {
         
         let runner = TestRunner::new();
-        let test_result = runner.execute_test("ping 1.1.1.1").await;
+        let test_result = runner.execute_test("ping 1.1.1.1", &ThresholdConfig::default()).await;
 
         assert_eq!(test_result.is_ok(), true);
         let result = test_result.unwrap();
@@ -127,22 +187,137 @@ mod tests {
         assert_eq!(result.metrics.is_some(), true);
         let metrics = result.metrics.unwrap();
 
-        assert_eq!(metrics.len(), 1);
+        assert_eq!(metrics.len(), 6);
         assert_eq!(metrics.get("ping_rtt").unwrap() > &0.00, true);
+        assert_eq!(metrics.get("ping_loss_pct"), Some(&0.00));
+    }
+
+    #[tokio::test]
+    async fn should_ping_loopback() {
+
+        let runner = TestRunner::new();
+        let result = runner.execute_test("ping 127.0.0.1", &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.target, "127.0.0.1");
+        assert_eq!(result.result, ResultCategory::Success);
+        assert!(result.metrics.unwrap().contains_key("ping_rtt"));
     }
 
     #[tokio::test]
     async fn should_fail_invalid_ping() {
         
         let runner = TestRunner::new();
-        assert_eq!(runner.execute_test("ping 10.99.99.99").await, Ok(TestResult::fail("10.99.99.99")));
+        let result = runner.execute_test("ping 10.99.99.99", &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.target, "10.99.99.99");
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.metrics.unwrap().get("ping_loss_pct"), Some(&100.00));
+    }
+
+    #[tokio::test]
+    async fn should_dispatch_tcp_test() {
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+
+        let runner = TestRunner::new();
+        let result = runner.execute_test(&format!("tcp {}", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.target, target);
+        assert!(result.metrics.unwrap().contains_key("tcp_latency"));
+    }
+
+    #[tokio::test]
+    async fn should_apply_group_thresholds() {
+
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+
+        let thresholds = ThresholdConfig {
+            warn_latency_ms: Some(0),
+            ..ThresholdConfig::default()
+        };
+
+        let runner = TestRunner::new();
+        let result = runner.execute_test(&format!("tcp {}", target), &thresholds).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Warning);
+    }
+
+    #[test]
+    fn should_parse_tests_without_execution() {
+
+        let runner = TestRunner::new();
+
+        assert!(runner.parse_test("ping 10.99.99.99 count=3").is_ok());
+        assert!(runner.parse_test("http example.org status=2xx").is_ok());
+        assert!(runner.parse_test("tcp example.org:22").is_ok());
+        assert!(runner.parse_test("ping 10.99.99.99 count=0").is_err());
+        assert!(runner.parse_test("tcp example.org").is_err());
+        assert_eq!(runner.parse_test("pign 1.1.1.1"), Err(Error::basic(
+            "Test 'pign 1.1.1.1' failed, command not found".to_string()
+        )));
+    }
+
+    /// Custom test type, always successful with the target length as metric
+    struct LengthTest {}
+
+    #[async_trait]
+    impl Test for LengthTest {
+
+        fn matches(&self, test: &str) -> bool {
+
+            matches_type(test, "length")
+        }
+
+        fn parse(&self, test: &str) -> Result<(), Error> {
+
+            match test.split(' ').nth(1) {
+                Some(_) => Ok(()),
+                None => Err(Error::basic("Length test expects a target"))
+            }
+        }
+
+        async fn execute(&self, test: &str, _thresholds: &ThresholdConfig) -> Result<TestResult, Error> {
+
+            self.parse(test)?;
+            let target = test.split(' ').nth(1).unwrap_or_default();
+
+            let metrics = std::collections::HashMap::from([("length".to_string(), target.len() as f32)]);
+            Ok(TestResult::build(target, ResultCategory::Success, Some(metrics)))
+        }
+
+    }
+
+    #[tokio::test]
+    async fn should_dispatch_registered_test() {
+
+        let mut runner = TestRunner::new();
+        runner.register(LengthTest {});
+
+        assert!(runner.parse_test("length example").is_ok());
+        assert!(runner.parse_test("length").is_err());
+
+        let result = runner.execute_test("length example", &ThresholdConfig::default()).await.unwrap();
+        assert_eq!(result.metrics.unwrap().get("length"), Some(&7.0));
+    }
+
+    #[tokio::test]
+    async fn should_only_dispatch_registered_tests() {
+
+        let mut runner = TestRunner::empty();
+        assert!(runner.parse_test("ping 1.1.1.1").is_err());
+
+        runner.register(LengthTest {});
+        assert!(runner.parse_test("length example").is_ok());
+        assert!(runner.parse_test("ping 1.1.1.1").is_err());
     }
 
     #[tokio::test]
     async fn should_fail_unknown_test_type() {
         
         let runner = TestRunner::new();
-        assert_eq!(runner.execute_test("unknown").await, Err(Error::basic(
+        assert_eq!(runner.execute_test("unknown", &ThresholdConfig::default()).await, Err(Error::basic(
             "Test 'unknown' failed, command not found".to_string()
         )));
     }
@@ -151,7 +326,7 @@ mod tests {
     async fn should_fail_empty_test() {
         
         let runner = TestRunner::new();
-        assert_eq!(runner.execute_test("").await, Err(Error::basic(
+        assert_eq!(runner.execute_test("", &ThresholdConfig::default()).await, Err(Error::basic(
             "Test '' failed, command not found".to_string()
         )));
     }
@@ -0,0 +1,205 @@
+use std::{str, collections::HashMap, time::Duration};
+use tokio::{net::{lookup_host, TcpStream}, time::{timeout, Instant}};
+
+use async_trait::async_trait;
+
+use crate::{common::error::Error, relay::model::{FailureReason, ResultCategory, TestResult}, server::config::{parse_to_milliseconds, ThresholdConfig}};
+
+use super::{runner::Test, utils::{connect_failure, latency_category, matches_type, split_components}};
+
+const DEFAULT_TCP_TIMEOUT_MS: u64 = 2000;
+const DEFAULT_TCP_WARN_MS: u64 = 100;
+
+/// Options accepted by a TCP test, written as 'key=value' pairs after the
+/// target (for example 'tcp db.local:5432 warn_latency=50ms fail_latency=500ms timeout=1s').
+struct TcpOptions {
+    warn_latency_ms: Option<u64>,
+    fail_latency_ms: Option<u64>,
+    timeout_ms: u64
+}
+
+pub struct TcpTest {}
+
+impl TcpTest {
+
+    pub fn new() -> Self {
+
+        TcpTest {}
+    }
+
+}
+
+#[async_trait]
+impl Test for TcpTest {
+
+    fn matches(&self, test: &str) -> bool {
+
+        matches_type(test, "tcp")
+    }
+
+    /// Validate a TCP test command without opening any connection
+    fn parse(&self, test: &str) -> Result<(), Error> {
+
+        parse_test(test, &ThresholdConfig::default()).map(|_| ())
+    }
+
+    async fn execute(&self, test: &str, thresholds: &ThresholdConfig) -> Result<TestResult, Error> {
+
+        let (target, options) = parse_test(test, thresholds)?;
+        let target = target.as_str();
+
+        let connect_timeout = Duration::from_millis(options.timeout_ms);
+
+        // The target is resolved before measuring the latency, the TCP metric should
+        // only cover the handshake (and not the DNS resolution)
+        let socket_addr = match timeout(connect_timeout, lookup_host(target)).await {
+            Ok(Ok(mut addresses)) => addresses.next(),
+            Ok(Err(err)) => {
+                return Ok(TestResult::fail(target).with_reason(FailureReason::DnsResolution).with_message(err.to_string()));
+            },
+            Err(_elapsed) => {
+                return Ok(TestResult::fail(target).with_reason(FailureReason::DnsResolution).with_message("resolution timed out"));
+            }
+        };
+
+        let socket_addr = match socket_addr {
+            Some(socket_addr) => socket_addr,
+            None => return Ok(TestResult::fail(target).with_reason(FailureReason::DnsResolution).with_message("no address found"))
+        };
+
+        let latency_chrono = Instant::now();
+        let connect_result = timeout(connect_timeout, TcpStream::connect(socket_addr)).await;
+        let duration = latency_chrono.elapsed();
+
+        match connect_result {
+            Ok(Ok(_stream)) => {
+
+                let duration_ms: f32 = duration.as_micros() as f32 / 1000.0;
+
+                let category = latency_category(duration_ms, options.warn_latency_ms, options.fail_latency_ms);
+
+                let metrics: HashMap<String, f32> = HashMap::from([
+                    ("tcp_latency".to_string(), duration_ms)
+                ]);
+
+                let result = TestResult::build(target, category, Some(metrics));
+                match category {
+                    ResultCategory::Success => Ok(result),
+                    _ => Ok(result.with_reason(FailureReason::Latency { latency_ms: duration_ms }))
+                }
+
+            },
+            // Connection refused or unreachable host
+            Ok(Err(err)) => Ok(TestResult::fail(target).with_reason(connect_failure(&err)).with_message(err.to_string())),
+            Err(_elapsed) => Ok(TestResult::fail(target).with_reason(FailureReason::Timeout))
+        }
+    }
+
+}
+
+fn parse_test(test: &str, thresholds: &ThresholdConfig) -> Result<(String, TcpOptions), Error> {
+
+    let tcp_components = split_components(test);
+
+    let target = tcp_components.get(1)
+        .ok_or(Error::new("TCP test failed", "The TCP command expects a 'host:port' target"))?;
+
+    let has_port = target.rsplit_once(':').map(|(_, port)| port.parse::<u16>().is_ok()).unwrap_or(false);
+    if !has_port {
+        return Err(Error::new("TCP test failed", format!("Expected a 'host:port' target, found '{}'", target)));
+    }
+
+    let options = parse_options(&tcp_components[2..], thresholds)?;
+
+    Ok((target.to_string(), options))
+}
+
+fn parse_options(option_components: &[String], thresholds: &ThresholdConfig) -> Result<TcpOptions, Error> {
+
+    let mut options = TcpOptions {
+        warn_latency_ms: thresholds.warn_latency_ms.or(Some(DEFAULT_TCP_WARN_MS)),
+        fail_latency_ms: thresholds.fail_latency_ms,
+        timeout_ms: DEFAULT_TCP_TIMEOUT_MS
+    };
+
+    for option in option_components {
+
+        let (key, value) = option.split_once('=')
+            .ok_or_else(|| Error::new("TCP test failed", format!("Expected a 'key=value' option, found '{}'", option)))?;
+
+        let milliseconds = parse_to_milliseconds(value)
+            .map_err(|err| Error::new("TCP test failed", format!("Option '{}' has an {}", key, err)))?;
+
+        match key {
+            "warn_latency" => options.warn_latency_ms = Some(milliseconds),
+            "fail_latency" => options.fail_latency_ms = Some(milliseconds),
+            "timeout" => options.timeout_ms = milliseconds,
+            _ => {
+                return Err(Error::new("TCP test failed", format!("Unknown TCP option '{}'", key)));
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tokio::net::TcpListener;
+
+    use super::*;
+
+    #[tokio::test]
+    async fn should_connect_open_port() {
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+
+        let result = TcpTest::new().execute(&format!("tcp {}", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.target, target);
+        assert_eq!(result.result, ResultCategory::Success);
+        assert!(result.metrics.unwrap().contains_key("tcp_latency"));
+    }
+
+    #[tokio::test]
+    async fn should_apply_latency_thresholds() {
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+
+        let result = TcpTest::new().execute(&format!("tcp {} warn_latency=0ms", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Warning);
+
+        let result = TcpTest::new().execute(&format!("tcp {} fail_latency=0ms", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+    }
+
+    #[tokio::test]
+    async fn should_fail_refused_port() {
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let target = listener.local_addr().unwrap().to_string();
+        drop(listener);
+
+        let result = TcpTest::new().execute(&format!("tcp {}", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::ConnectionRefused));
+    }
+
+    #[tokio::test]
+    async fn should_deny_invalid_target() {
+
+        let tcp = TcpTest::new();
+
+        assert!(tcp.execute("tcp", &ThresholdConfig::default()).await.is_err());
+        assert!(tcp.execute("tcp 127.0.0.1", &ThresholdConfig::default()).await.is_err());
+        assert!(tcp.execute("tcp 127.0.0.1:22 timeout=abc", &ThresholdConfig::default()).await.is_err());
+        assert!(tcp.execute("tcp 127.0.0.1:22 unknown=1s", &ThresholdConfig::default()).await.is_err());
+    }
+
+}
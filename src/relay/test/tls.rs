@@ -0,0 +1,381 @@
+use std::{str, collections::HashMap, convert::TryFrom, net::IpAddr, sync::Arc, time::Duration};
+use tokio::{net::TcpStream, time::{timeout, Instant}};
+
+use chrono::Utc;
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+use x509_parser::parse_x509_certificate;
+use async_trait::async_trait;
+
+use crate::{common::error::Error, relay::model::{FailureReason, TestResult, ResultCategory}, server::config::{parse_to_milliseconds, ThresholdConfig}};
+
+use super::{runner::Test, utils::{connect_failure, latency_category, matches_type, split_components}};
+
+const DEFAULT_TLS_PORT: u16 = 443;
+const DEFAULT_TLS_TIMEOUT_MS: u64 = 5000;
+const DEFAULT_TLS_WARN_DAYS: i64 = 14;
+
+/// Options accepted by a TLS test, written as 'key=value' pairs after the
+/// target (for example 'tls kongbytes.io:443 warn_days=30 timeout=2s'). Latency
+/// thresholds apply to the TCP connection and TLS handshake duration.
+struct TlsOptions {
+    warn_days: i64,
+    warn_latency_ms: Option<u64>,
+    fail_latency_ms: Option<u64>,
+    timeout_ms: u64
+}
+
+pub struct TlsTest {
+    connector: TlsConnector
+}
+
+impl TlsTest {
+
+    pub fn new() -> Self {
+
+        // Use the same Mozilla root certificates as the HTTP client (reqwest is
+        // built with the 'rustls' and 'webpki-roots' stack)
+        let mut root_store = RootCertStore::empty();
+        root_store.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|trust_anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                trust_anchor.subject,
+                trust_anchor.spki,
+                trust_anchor.name_constraints
+            )
+        }));
+
+        TlsTest::with_root_store(root_store)
+    }
+
+    fn with_root_store(root_store: RootCertStore) -> Self {
+
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+
+        TlsTest {
+            connector: TlsConnector::from(Arc::new(client_config))
+        }
+    }
+
+}
+
+#[async_trait]
+impl Test for TlsTest {
+
+    fn matches(&self, test: &str) -> bool {
+
+        matches_type(test, "tls")
+    }
+
+    /// Validate a TLS test command without opening any connection
+    fn parse(&self, test: &str) -> Result<(), Error> {
+
+        let (target, _) = parse_test(test, &ThresholdConfig::default())?;
+        parse_target(&target).map(|_| ())
+    }
+
+    async fn execute(&self, test: &str, thresholds: &ThresholdConfig) -> Result<TestResult, Error> {
+
+        let (target, options) = parse_test(test, thresholds)?;
+        let target = target.as_str();
+
+        let (host, port, server_name) = parse_target(target)?;
+        let handshake_timeout = Duration::from_millis(options.timeout_ms);
+
+        let latency_chrono = Instant::now();
+        let tcp_stream = match timeout(handshake_timeout, TcpStream::connect((host, port))).await {
+            Ok(Ok(tcp_stream)) => tcp_stream,
+            Ok(Err(err)) => return Ok(TestResult::fail(target).with_reason(connect_failure(&err)).with_message(err.to_string())),
+            Err(_elapsed) => return Ok(TestResult::fail(target).with_reason(FailureReason::Timeout))
+        };
+
+        // The handshake verifies the certificate chain against the trusted roots, the
+        // validity period and the hostname: an expired, untrusted or mismatched certificate
+        // fails the handshake (and therefore the test)
+        let tls_stream = match timeout(handshake_timeout, self.connector.connect(server_name, tcp_stream)).await {
+            Ok(Ok(tls_stream)) => tls_stream,
+            Ok(Err(err)) => return Ok(TestResult::fail(target).with_reason(FailureReason::Tls).with_message(err.to_string())),
+            Err(_elapsed) => return Ok(TestResult::fail(target).with_reason(FailureReason::Timeout))
+        };
+        let duration_ms: f32 = latency_chrono.elapsed().as_micros() as f32 / 1000.0;
+
+        let (_, connection) = tls_stream.get_ref();
+        let peer_certificate = connection.peer_certificates()
+            .and_then(|certificates| certificates.first())
+            .ok_or_else(|| Error::new("TLS test failed", "The server did not present any certificate"))?;
+
+        let (_, certificate) = parse_x509_certificate(&peer_certificate.0)
+            .map_err(|err| Error::new("TLS test failed", format!("Could not parse server certificate ({})", err)))?;
+
+        let remaining_seconds = certificate.validity().not_after.timestamp() - Utc::now().timestamp();
+        let days_remaining = remaining_seconds.div_euclid(24 * 60 * 60);
+
+        let expiry_category = if days_remaining < options.warn_days {
+            ResultCategory::Warning
+        } else {
+            ResultCategory::Success
+        };
+
+        let metrics: HashMap<String, f32> = HashMap::from([
+            ("tls_days_remaining".to_string(), days_remaining as f32),
+            ("tls_latency".to_string(), duration_ms)
+        ]);
+
+        let latency_category = latency_category(duration_ms, options.warn_latency_ms, options.fail_latency_ms);
+        if latency_category > expiry_category {
+            let result = TestResult::build(target, latency_category, Some(metrics));
+            return Ok(result.with_reason(FailureReason::Latency { latency_ms: duration_ms }));
+        }
+
+        let result = TestResult::build(target, expiry_category, Some(metrics));
+        match expiry_category {
+            ResultCategory::Success => Ok(result),
+            _ => Ok(result.with_reason(FailureReason::Tls).with_message(format!("certificate expires in {} days", days_remaining)))
+        }
+    }
+
+}
+
+fn parse_test(test: &str, thresholds: &ThresholdConfig) -> Result<(String, TlsOptions), Error> {
+
+    let tls_components = split_components(test);
+
+    let target = tls_components.get(1)
+        .ok_or(Error::new("TLS test failed", "The TLS command expects a 'host:port' target"))?;
+
+    parse_target(target)?;
+    let options = parse_options(&tls_components[2..], thresholds)?;
+
+    Ok((target.to_string(), options))
+}
+
+/// Split a 'host[:port]' target (IPv6 addresses are written between brackets), the
+/// host must be a DNS name: certificates are not verified against IP addresses
+fn parse_target(target: &str) -> Result<(&str, u16, ServerName), Error> {
+
+    let parse_port = |port: &str| port.parse::<u16>()
+        .map_err(|_| Error::new("TLS test failed", format!("Invalid port in target '{}'", target)));
+
+    let (host, port) = match target.strip_prefix('[') {
+        Some(bracketed_target) => {
+
+            let (host, remaining) = bracketed_target.split_once(']')
+                .ok_or_else(|| Error::new("TLS test failed", format!("Missing closing bracket in target '{}'", target)))?;
+
+            match remaining.strip_prefix(':') {
+                Some(port) => (host, parse_port(port)?),
+                None if remaining.is_empty() => (host, DEFAULT_TLS_PORT),
+                None => return Err(Error::new("TLS test failed", format!("Invalid port in target '{}'", target)))
+            }
+        },
+        None => match target.rsplit_once(':') {
+            Some((host, port)) => (host, parse_port(port)?),
+            None => (target, DEFAULT_TLS_PORT)
+        }
+    };
+
+    if host.parse::<IpAddr>().is_ok() || host.contains(':') {
+        return Err(Error::new("TLS test failed", format!("Only DNS names are supported as TLS target, found IP address '{}'", host)));
+    }
+
+    let server_name = ServerName::try_from(host)
+        .map_err(|_| Error::new("TLS test failed", format!("Invalid server name '{}'", host)))?;
+
+    Ok((host, port, server_name))
+}
+
+fn parse_options(option_components: &[String], thresholds: &ThresholdConfig) -> Result<TlsOptions, Error> {
+
+    let mut options = TlsOptions {
+        warn_days: DEFAULT_TLS_WARN_DAYS,
+        warn_latency_ms: thresholds.warn_latency_ms,
+        fail_latency_ms: thresholds.fail_latency_ms,
+        timeout_ms: DEFAULT_TLS_TIMEOUT_MS
+    };
+
+    for option in option_components {
+
+        let (key, value) = option.split_once('=')
+            .ok_or_else(|| Error::new("TLS test failed", format!("Expected a 'key=value' option, found '{}'", option)))?;
+
+        let parse_milliseconds = |value: &str| parse_to_milliseconds(value)
+            .map_err(|err| Error::new("TLS test failed", format!("Option '{}' has an {}", key, err)));
+
+        match key {
+            "warn_days" => {
+                options.warn_days = value.parse::<i64>()
+                    .map_err(|_| Error::new("TLS test failed", format!("Option 'warn_days' expects a number of days, found '{}'", value)))?;
+            },
+            "warn_latency" => {
+                options.warn_latency_ms = Some(parse_milliseconds(value)?);
+            },
+            "fail_latency" => {
+                options.fail_latency_ms = Some(parse_milliseconds(value)?);
+            },
+            "timeout" => {
+                options.timeout_ms = parse_milliseconds(value)?;
+            },
+            _ => {
+                return Err(Error::new("TLS test failed", format!("Unknown TLS option '{}'", key)));
+            }
+        }
+    }
+
+    Ok(options)
+}
+
+#[cfg(test)]
+mod tests {
+
+    use rcgen::{BasicConstraints, Certificate, CertificateParams, IsCa, date_time_ymd};
+    use tokio::{io::AsyncWriteExt, net::TcpListener};
+    use tokio_rustls::TlsAcceptor;
+    use tokio_rustls::rustls::{self, PrivateKey, ServerConfig};
+
+    use super::*;
+
+    /// Generate a local certificate authority, and a certificate for the given
+    /// hostname signed by that authority with the given expiry year
+    fn generate_certificates(hostname: &str, expiry_year: i32) -> (Certificate, Certificate) {
+
+        let mut ca_params = CertificateParams::new(vec![]);
+        ca_params.is_ca = IsCa::Ca(BasicConstraints::Unconstrained);
+        let ca = Certificate::from_params(ca_params).unwrap();
+
+        let mut leaf_params = CertificateParams::new(vec![hostname.to_string()]);
+        leaf_params.not_before = date_time_ymd(2000, 1, 1);
+        leaf_params.not_after = date_time_ymd(expiry_year, 1, 1);
+        let leaf = Certificate::from_params(leaf_params).unwrap();
+
+        (ca, leaf)
+    }
+
+    /// Launch a local TLS server presenting the leaf certificate and return
+    /// the 'port' it is listening on
+    async fn launch_tls_server(ca: &Certificate, leaf: &Certificate) -> u16 {
+
+        let certificate_chain = vec![rustls::Certificate(leaf.serialize_der_with_signer(ca).unwrap())];
+        let private_key = PrivateKey(leaf.serialize_private_key_der());
+
+        let server_config = ServerConfig::builder()
+            .with_safe_defaults()
+            .with_no_client_auth()
+            .with_single_cert(certificate_chain, private_key)
+            .unwrap();
+        let acceptor = TlsAcceptor::from(Arc::new(server_config));
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                if let Ok(mut tls_stream) = acceptor.accept(stream).await {
+                    let _ = tls_stream.shutdown().await;
+                }
+            }
+        });
+
+        port
+    }
+
+    fn build_trusting_test(ca: &Certificate) -> TlsTest {
+
+        let mut root_store = RootCertStore::empty();
+        root_store.add(&rustls::Certificate(ca.serialize_der().unwrap())).unwrap();
+
+        TlsTest::with_root_store(root_store)
+    }
+
+    #[tokio::test]
+    async fn should_report_days_remaining() {
+
+        let (ca, leaf) = generate_certificates("localhost", 2100);
+        let port = launch_tls_server(&ca, &leaf).await;
+
+        let target = format!("localhost:{}", port);
+        let result = build_trusting_test(&ca).execute(&format!("tls {}", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.target, target);
+        assert_eq!(result.result, ResultCategory::Success);
+        assert!(result.metrics.unwrap().get("tls_days_remaining").unwrap() > &365.0);
+    }
+
+    #[tokio::test]
+    async fn should_warn_under_days_threshold() {
+
+        let (ca, leaf) = generate_certificates("localhost", 2100);
+        let port = launch_tls_server(&ca, &leaf).await;
+
+        let test = format!("tls localhost:{} warn_days=100000", port);
+        let result = build_trusting_test(&ca).execute(&test, &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Warning);
+    }
+
+    #[tokio::test]
+    async fn should_fail_expired_certificate() {
+
+        let (ca, leaf) = generate_certificates("localhost", 2001);
+        let port = launch_tls_server(&ca, &leaf).await;
+
+        let target = format!("localhost:{}", port);
+        let result = build_trusting_test(&ca).execute(&format!("tls {}", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::Tls));
+    }
+
+    #[tokio::test]
+    async fn should_fail_untrusted_certificate() {
+
+        let (ca, leaf) = generate_certificates("localhost", 2100);
+        let port = launch_tls_server(&ca, &leaf).await;
+
+        let target = format!("localhost:{}", port);
+        let result = TlsTest::new().execute(&format!("tls {}", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::Tls));
+    }
+
+    #[tokio::test]
+    async fn should_fail_hostname_mismatch() {
+
+        let (ca, leaf) = generate_certificates("watchdog.example", 2100);
+        let port = launch_tls_server(&ca, &leaf).await;
+
+        let target = format!("localhost:{}", port);
+        let result = build_trusting_test(&ca).execute(&format!("tls {}", target), &ThresholdConfig::default()).await.unwrap();
+
+        assert_eq!(result.result, ResultCategory::Fail);
+        assert_eq!(result.reason, Some(FailureReason::Tls));
+    }
+
+    #[tokio::test]
+    async fn should_deny_invalid_options() {
+
+        let tls = TlsTest::new();
+
+        assert!(tls.execute("tls", &ThresholdConfig::default()).await.is_err());
+        assert!(tls.execute("tls localhost:https", &ThresholdConfig::default()).await.is_err());
+        assert!(tls.execute("tls localhost:443 warn_days=soon", &ThresholdConfig::default()).await.is_err());
+        assert!(tls.execute("tls localhost:443 unknown=1", &ThresholdConfig::default()).await.is_err());
+        assert!(tls.execute("tls [localhost:443", &ThresholdConfig::default()).await.is_err());
+    }
+
+    #[test]
+    fn should_deny_ip_address_targets() {
+
+        let tls = TlsTest::new();
+
+        for test in ["tls 10.0.0.1", "tls 10.0.0.1:8443", "tls [::1]:443", "tls [::1]", "tls ::1"] {
+            let error = tls.parse(test).unwrap_err();
+            assert!(error.details.unwrap().starts_with("Only DNS names are supported as TLS target"), "{}", test);
+        }
+
+        assert!(tls.parse("tls [localhost]:8443").is_ok());
+    }
+
+}
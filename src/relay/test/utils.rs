@@ -0,0 +1,157 @@
+use std::{error, io};
+
+use crate::relay::model::{FailureReason, ResultCategory};
+
+/**
+ * Split a test command into components separated by spaces. Double quotes can
+ * be used to keep spaces in a component, for example the HTTP test command
+ * 'http example.org header="Authorization: Bearer token"' is split into three
+ * components (the quotes are removed). A literal double quote is written '\"'.
+ */
+pub fn split_components(test: &str) -> Vec<String> {
+
+    let mut components: Vec<String> = vec![];
+    let mut current = String::new();
+    let mut in_quotes = false;
+
+    let mut characters = test.chars().peekable();
+    while let Some(character) = characters.next() {
+
+        match character {
+            '\\' if characters.peek() == Some(&'"') => {
+                current.push('"');
+                characters.next();
+            },
+            '"' => in_quotes = !in_quotes,
+            ' ' if !in_quotes => {
+                if !current.is_empty() {
+                    components.push(current);
+                    current = String::new();
+                }
+            },
+            _ => current.push(character)
+        }
+    }
+
+    if !current.is_empty() {
+        components.push(current);
+    }
+
+    components
+}
+
+/// Check if the first component of a test command is the given test type, for
+/// example 'http example.org' has the 'http' type
+pub fn matches_type(test: &str, test_type: &str) -> bool {
+
+    test.split_whitespace().next() == Some(test_type)
+}
+
+/// Category of a latency measure, given optional warning and failure
+/// thresholds (thresholds are inclusive)
+pub fn latency_category(latency_ms: f32, warn_latency_ms: Option<u64>, fail_latency_ms: Option<u64>) -> ResultCategory {
+
+    let exceeds = |threshold_ms: Option<u64>| threshold_ms.map(|threshold| latency_ms >= threshold as f32).unwrap_or(false);
+
+    if exceeds(fail_latency_ms) {
+        ResultCategory::Fail
+    } else if exceeds(warn_latency_ms) {
+        ResultCategory::Warning
+    } else {
+        ResultCategory::Success
+    }
+}
+
+/// Failure reason of a connection that could not be established
+pub fn connect_failure(err: &io::Error) -> FailureReason {
+
+    match err.kind() {
+        io::ErrorKind::ConnectionRefused => FailureReason::ConnectionRefused,
+        io::ErrorKind::TimedOut => FailureReason::Timeout,
+        _ => FailureReason::Unreachable
+    }
+}
+
+/// Format an error with all its sources, libraries such as reqwest only
+/// display the top-level error (without the actual cause)
+pub fn format_error_chain(err: &dyn error::Error) -> String {
+
+    let mut formatted = err.to_string();
+
+    let mut source = err.source();
+    while let Some(cause) = source {
+
+        let cause_message = cause.to_string();
+        if !formatted.contains(&cause_message) {
+            formatted.push_str(": ");
+            formatted.push_str(&cause_message);
+        }
+        source = cause.source();
+    }
+
+    formatted
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_split_on_spaces() {
+
+        assert_eq!(split_components("ping  1.1.1.1"), vec!["ping", "1.1.1.1"]);
+    }
+
+    #[test]
+    fn should_keep_quoted_spaces() {
+
+        assert_eq!(
+            split_components("http example.org header=\"Authorization: Bearer token\" method=POST"),
+            vec!["http", "example.org", "header=Authorization: Bearer token", "method=POST"]
+        );
+    }
+
+    #[test]
+    fn should_keep_escaped_quotes() {
+
+        assert_eq!(
+            split_components(r#"http example.org body_json="$.status == \"ok\"""#),
+            vec!["http", "example.org", r#"body_json=$.status == "ok""#]
+        );
+    }
+
+    #[test]
+    fn should_split_empty_test() {
+
+        assert_eq!(split_components(""), Vec::<String>::new());
+    }
+
+    #[test]
+    fn should_match_test_type() {
+
+        assert!(matches_type("http example.org", "http"));
+        assert!(matches_type("  ping 1.1.1.1", "ping"));
+        assert!(!matches_type("https example.org", "http"));
+        assert!(!matches_type("", "http"));
+    }
+
+    #[test]
+    fn should_categorize_latency() {
+
+        assert_eq!(latency_category(50.0, None, None), ResultCategory::Success);
+        assert_eq!(latency_category(50.0, Some(100), Some(200)), ResultCategory::Success);
+        assert_eq!(latency_category(100.0, Some(100), Some(200)), ResultCategory::Warning);
+        assert_eq!(latency_category(250.0, Some(100), Some(200)), ResultCategory::Fail);
+        assert_eq!(latency_category(250.0, None, Some(200)), ResultCategory::Fail);
+    }
+
+    #[test]
+    fn should_categorize_connect_failure() {
+
+        assert_eq!(connect_failure(&io::Error::from(io::ErrorKind::ConnectionRefused)), FailureReason::ConnectionRefused);
+        assert_eq!(connect_failure(&io::Error::from(io::ErrorKind::TimedOut)), FailureReason::Timeout);
+        assert_eq!(connect_failure(&io::Error::from(io::ErrorKind::PermissionDenied)), FailureReason::Unreachable);
+    }
+
+}
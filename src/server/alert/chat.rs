@@ -0,0 +1,71 @@
+use crate::common::utils::format_duration;
+use crate::server::config::AlertSeverity;
+
+use super::manager::{Alert, AlertKind};
+
+/// Username displayed by the chat platforms that allow overriding the webhook name
+pub const CHAT_USERNAME: &str = "watchdog";
+
+/// Color of the alert message: red for critical incidents, orange for warnings,
+/// green for recoveries and grey for test messages
+pub fn alert_color(alert: &Alert) -> u32 {
+
+    match (alert.kind, alert.severity) {
+        (AlertKind::Incident, AlertSeverity::Critical) => 0xD63232,
+        (AlertKind::Incident, AlertSeverity::Warning) => 0xE8A317,
+        (AlertKind::Recovery, _) => 0x2EB886,
+        (AlertKind::Test, _) => 0x808080
+    }
+}
+
+pub fn hex_color(color: u32) -> String {
+    format!("#{:06X}", color)
+}
+
+/// Fields displayed next to the alert message (name and value), fields without
+/// value are not included
+pub fn alert_fields(alert: &Alert) -> Vec<(&'static str, String)> {
+
+    let mut fields: Vec<(&'static str, String)> = vec![];
+
+    if !alert.region.is_empty() {
+        fields.push(("Region", alert.region.clone()));
+    }
+    if let Some(group) = &alert.group {
+        fields.push(("Group", group.clone()));
+    }
+    if alert.kind == AlertKind::Incident {
+        fields.push(("Severity", alert.field_value("severity").as_str().unwrap_or_default().to_string()));
+    }
+    if let Some(duration_secs) = alert.duration_secs {
+        fields.push(("Outage duration", format_duration(duration_secs)));
+    }
+
+    fields
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_describe_alert() {
+
+        let incident = Alert::incident("region-north", Some("default"), AlertSeverity::Warning, "Group region-north.default is DOWN");
+        assert_eq!(hex_color(alert_color(&incident)), "#E8A317");
+        assert_eq!(alert_fields(&incident), vec![
+            ("Region", "region-north".to_string()),
+            ("Group", "default".to_string()),
+            ("Severity", "warning".to_string())
+        ]);
+
+        let recovery = Alert::recovery("region-north", None, AlertSeverity::Critical, "Region region-north is UP again", 3720);
+        assert_eq!(hex_color(alert_color(&recovery)), "#2EB886");
+        assert_eq!(alert_fields(&recovery), vec![
+            ("Region", "region-north".to_string()),
+            ("Outage duration", "1h 02m".to_string())
+        ]);
+    }
+
+}
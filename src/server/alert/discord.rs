@@ -0,0 +1,98 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::common::error::Error;
+
+use super::chat::{alert_color, alert_fields, CHAT_USERNAME};
+use super::manager::{build_http_client, send_request, Alert, AlertMedium};
+
+/// Discord webhook, the alert is sent as a colored embed
+pub struct DiscordAlerter {
+
+    id: String,
+    webhook_url: String,
+    http_client: Client
+
+}
+
+impl DiscordAlerter {
+
+    pub fn new<M>(id: M, webhook_url: M) -> Self where M: Into<String> {
+
+        DiscordAlerter {
+            id: id.into(),
+            webhook_url: webhook_url.into(),
+            http_client: build_http_client()
+        }
+    }
+
+}
+
+fn build_embed(alert: &Alert) -> Value {
+
+    let fields: Vec<Value> = alert_fields(alert).into_iter()
+        .map(|(name, value)| json!({ "name": name, "value": value, "inline": true }))
+        .collect();
+
+    json!({
+        "title": alert.message,
+        "color": alert_color(alert),
+        "fields": fields,
+        "timestamp": alert.timestamp.to_rfc3339()
+    })
+}
+
+fn build_message(alert: &Alert) -> Value {
+
+    json!({
+        "username": CHAT_USERNAME,
+        "embeds": [build_embed(alert)]
+    })
+}
+
+#[async_trait]
+impl AlertMedium for DiscordAlerter {
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+
+        let request = self.http_client
+            .post(&self.webhook_url)
+            .json(&build_message(alert));
+
+        send_request(&self.id, request).await
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::server::config::AlertSeverity;
+
+    use super::*;
+
+    #[test]
+    fn should_build_discord_message() {
+
+        let alert = Alert::recovery("region-north", Some("default"), AlertSeverity::Warning, "Group region-north.default is UP again", 125);
+        let message = build_message(&alert);
+
+        assert_eq!(message["username"], CHAT_USERNAME);
+
+        let embed = &message["embeds"][0];
+        assert_eq!(embed["title"], "Group region-north.default is UP again");
+        assert_eq!(embed["color"], 0x2EB886);
+        assert_eq!(embed["timestamp"], alert.timestamp.to_rfc3339());
+        assert_eq!(embed["fields"], json!([
+            { "name": "Region", "value": "region-north", "inline": true },
+            { "name": "Group", "value": "default", "inline": true },
+            { "name": "Outage duration", "value": "2m 05s", "inline": true }
+        ]));
+    }
+
+}
@@ -1,72 +1,179 @@
-use std::{str, collections::HashMap, env};
+use std::{str, collections::{HashMap, HashSet}, env, time::Duration};
 
-use reqwest::RequestBuilder;
+use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use reqwest::{Client, RequestBuilder};
+use serde::Serialize;
+use serde_json::Value;
 
-use crate::{common::error::Error, server::config::AlertConfig};
+use crate::{common::error::Error, server::config::{AlertConfig, AlertRouteConfig, AlertSeverity}};
 
-use super::{telegram::TelegramAlerter, spryng::SpryngAlerter};
+use super::{telegram::TelegramAlerter, spryng::SpryngAlerter, webhook::WebhookAlerter};
+use super::{slack::SlackAlerter, discord::DiscordAlerter, mattermost::MattermostAlerter};
+use super::pagerduty::{PagerDutyAlerter, DEFAULT_PAGERDUTY_URL};
+use super::opsgenie::{OpsgenieAlerter, DEFAULT_OPSGENIE_URL};
+use super::smtp::{SmtpAlerter, SmtpSettings, DEFAULT_SMTP_PORT, DEFAULT_SMTP_SUBJECT};
 
+/// Timeout of the HTTP requests sent to the mediums, a medium that does not answer
+/// must not hold the alert delivery
+const REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Alert fields available as '{{field}}' placeholders in the medium templates
+pub const TEMPLATE_FIELDS: [&str; 7] = ["kind", "region", "group", "severity", "message", "timestamp", "duration"];
+
+#[async_trait]
 pub trait AlertMedium {
 
     fn get_id(&self) -> String;
 
-    fn build_request(&self, message: &str) -> RequestBuilder;
+    async fn send(&self, alert: &Alert) -> Result<(), Error>;
+
+}
+
+#[derive(Serialize,Clone,Copy,PartialEq,Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertKind {
+    Incident,
+    Recovery,
+    Test
+}
+
+/// Alert sent to the mediums, text mediums only send the message while the other
+/// fields are available to structured mediums (such as webhooks). The region, the
+/// group and the severity are also used to route the alert.
+#[derive(Serialize,Clone)]
+pub struct Alert {
+    pub kind: AlertKind,
+    pub region: String,
+    pub group: Option<String>,
+    pub severity: AlertSeverity,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub duration_secs: Option<i64>
+}
+
+impl Alert {
+
+    pub fn incident<M>(region: &str, group: Option<&str>, severity: AlertSeverity, message: M) -> Self where M: Into<String> {
+
+        Alert {
+            kind: AlertKind::Incident,
+            region: region.to_string(),
+            group: group.map(String::from),
+            severity,
+            message: message.into(),
+            timestamp: Utc::now(),
+            duration_secs: None
+        }
+    }
+
+    /// Recovery of a region or a group, with the duration of the outage
+    pub fn recovery<M>(region: &str, group: Option<&str>, severity: AlertSeverity, message: M, duration_secs: i64) -> Self where M: Into<String> {
+
+        Alert {
+            kind: AlertKind::Recovery,
+            duration_secs: Some(duration_secs),
+            ..Alert::incident(region, group, severity, message)
+        }
+    }
+
+    pub fn test<M>(message: M) -> Self where M: Into<String> {
+
+        Alert {
+            kind: AlertKind::Test,
+            ..Alert::incident("", None, AlertSeverity::Warning, message)
+        }
+    }
+
+    /// Stable key of the region or the group affected by the alert, incident management
+    /// mediums use it to resolve their incident when the region or the group recovers
+    pub fn dedup_key(&self) -> String {
+
+        match (self.kind, &self.group) {
+            (AlertKind::Test, _) => "watchdog/test".to_string(),
+            (_, Some(group)) => format!("watchdog/{}/{}", self.region, group),
+            (_, None) => format!("watchdog/{}", self.region)
+        }
+    }
+
+    /// Value of a template field (the group and the duration are null when
+    /// not available)
+    pub fn field_value(&self, field: &str) -> Value {
+
+        let value = match field {
+            "kind" => serde_json::to_value(self.kind),
+            "severity" => serde_json::to_value(self.severity),
+            "region" => Ok(Value::from(self.region.as_str())),
+            "group" => Ok(self.group.as_deref().map(Value::from).unwrap_or(Value::Null)),
+            "message" => Ok(Value::from(self.message.as_str())),
+            "timestamp" => Ok(Value::from(self.timestamp.to_rfc3339())),
+            "duration" => Ok(self.duration_secs.map(Value::from).unwrap_or(Value::Null)),
+            _ => Ok(Value::Null)
+        };
+
+        value.unwrap_or(Value::Null)
+    }
+
+    /// Replace the '{{field}}' placeholders of a text template, null fields are
+    /// replaced by an empty text
+    pub fn render(&self, template: &str) -> String {
+
+        let mut rendered_text = template.to_string();
+        for field in TEMPLATE_FIELDS {
+
+            let placeholder = format!("{{{{{}}}}}", field);
+            if rendered_text.contains(&placeholder) {
+
+                let field_text = match self.field_value(field) {
+                    Value::String(text) => text,
+                    Value::Null => String::new(),
+                    value => value.to_string()
+                };
+                rendered_text = rendered_text.replace(&placeholder, &field_text);
+            }
+        }
+
+        rendered_text
+    }
 
 }
 
 pub struct AlertManager {
 
-    mediums: HashMap<String, Box<dyn AlertMedium + Send + Sync + 'static>>
+    mediums: HashMap<String, Box<dyn AlertMedium + Send + Sync + 'static>>,
+
+    /// Mediums that only receive incident alerts (no recovery message)
+    recovery_disabled: HashSet<String>,
+
+    /// Without routes, every medium receives all alerts
+    routes: Vec<AlertRouteConfig>
 
 }
 
 impl AlertManager {
 
-    pub fn try_from_config(config: &[AlertConfig]) -> Result<Self, Error> {
+    pub fn try_from_config(config: &[AlertConfig], routes: &[AlertRouteConfig]) -> Result<Self, Error> {
 
         let mut manager = AlertManager {
-            mediums: HashMap::new()
+            mediums: HashMap::new(),
+            recovery_disabled: HashSet::new(),
+            routes: routes.to_vec()
         };
 
         for alerter in config.iter() {
 
-            if alerter.medium == "telegram" {
-    
-                let alerter_id = &alerter.name;
-    
-                let chat_env = alerter.chat_env.clone().ok_or(Error::basic("Expected 'chat_env' configuration with Telegram medium"))?;
-                let token_env = alerter.token_env.clone().ok_or(Error::basic("Expected 'token_env' configuration with Telegram medium"))?;
-    
-                let telegram_chat = env::var(chat_env).map_err(|_| Error::basic("Expected Telegram chat ID as environment variable"))?;
-                let telegram_token = env::var(token_env).map_err(|_| Error::basic("Expected Telegram token as environment variable"))?;
-    
-                let telegram = TelegramAlerter::new(alerter_id, &telegram_chat, &telegram_token);
-                manager.add_medium(telegram);
-    
-                continue;
+            if !alerter.recovery {
+                manager.recovery_disabled.insert(alerter.name.clone());
             }
-    
-            if alerter.medium == "spryng" {
-    
-                let alerter_id = &alerter.name;
-    
-                let recipients_env = alerter.recipients_env.clone().ok_or(Error::basic("Expected 'recipients_env' configuration with Spryng medium"))?;
-                let token_env = alerter.token_env.clone().ok_or(Error::basic("Expected 'token_env' configuration with Spryng medium"))?;
-    
-                let spring_recipients = env::var(recipients_env).map_err(|_| Error::basic("Expected Spryng SMS recipients as environment variable"))?;
-                let spryng_token = env::var(token_env).map_err(|_| Error::basic("Expected Spryng token as environment variable"))?;
-    
-                let formatted_recipients: Vec<String> = spring_recipients.split(',')
-                    .map(|recipient| recipient.trim().to_string())
-                    .collect();
-    
-                let spryng = SpryngAlerter::new(alerter_id, &spryng_token, formatted_recipients);
-                manager.add_medium(spryng);
-    
-                continue;
-            }
-    
-            Err(Error::basic(format!("Could not find provider {}", alerter.medium)))?;
+
+            let build_medium = MEDIUMS.iter()
+                .find(|(medium, _, _)| *medium == alerter.medium)
+                .map(|(_, _, build_medium)| build_medium)
+                .ok_or_else(|| Error::basic(format!("Could not find provider {}", alerter.medium)))?;
+
+            let medium = build_medium(alerter)?;
+            manager.mediums.insert(medium.get_id(), medium);
         }
 
         Ok(manager)
@@ -83,35 +190,323 @@ impl AlertManager {
         for medium_id in self.mediums.keys() {
 
             println!("Trigger test alert for medium {}", medium_id);
-            self.alert(Some(medium_id), "This is a watchdog monitoring test message").await?;
+            let medium = self.mediums.get(medium_id).ok_or_else(|| Error::basic("Could not find requested medium"))?;
+            medium.send(&Alert::test("This is a watchdog monitoring test message")).await?;
         }
 
         Ok(())
     }
 
-    pub async fn alert(&self, requested_medium_id: Option<&str>, message: &str) -> Result<(), Error> {
+    /// Send an alert to the mediums of all matching routes. Recovery messages (region or
+    /// group healthy again after an incident) reach the mediums that received the incident
+    /// alert, unless recovery messages are disabled on the medium.
+    pub async fn alert(&self, alert: &Alert) -> Result<(), Error> {
 
-        let medium = match requested_medium_id {
-            Some(medium_id) => self.mediums.get(medium_id).ok_or_else(|| Error::basic("Could not find requested medium"))?,
-            None => self.mediums.values().next().ok_or_else(|| Error::basic("Could not find default medium"))?,
-        };
+        let mut medium_ids = self.route_mediums(alert);
+
+        if alert.kind == AlertKind::Recovery {
+            medium_ids.retain(|medium_id| !self.recovery_disabled.contains(medium_id));
+        }
+        else if medium_ids.is_empty() {
+            return Err(Error::basic(format!("Could not find a medium for alerts on region {}", alert.region)));
+        }
+
+        self.send_all(&medium_ids, alert).await
+    }
+
+    /// Mediums of all routes matching the alert (each medium only once), or all
+    /// mediums when no route is configured
+    fn route_mediums(&self, alert: &Alert) -> Vec<String> {
+
+        if self.routes.is_empty() {
+            let mut medium_ids: Vec<String> = self.mediums.keys().cloned().collect();
+            medium_ids.sort();
+            return medium_ids;
+        }
+
+        let mut medium_ids: Vec<String> = vec![];
+        for route in self.routes.iter().filter(|route| route.matches(&alert.region, alert.group.as_deref(), alert.severity)) {
+
+            for alerter in route.alerters.iter() {
+                if self.mediums.contains_key(alerter) && !medium_ids.contains(alerter) {
+                    medium_ids.push(alerter.clone());
+                }
+            }
+        }
+
+        medium_ids
+    }
+
+    /// Send the message to every medium, a failing medium does not prevent
+    /// the message from reaching the other mediums
+    async fn send_all(&self, medium_ids: &[String], alert: &Alert) -> Result<(), Error> {
+
+        let mut failures: Vec<String> = vec![];
+        for medium_id in medium_ids {
+
+            if let Some(medium) = self.mediums.get(medium_id) {
+                if let Err(err) = medium.send(alert).await {
+                    failures.push(match err.details {
+                        Some(details) => format!("{}: {}", err.message, details),
+                        None => err.message
+                    });
+                }
+            }
+        }
+
+        if !failures.is_empty() {
+            let error_message = format!("Could not alert {} of {} medium(s): {}", failures.len(), medium_ids.len(), failures.join(", "));
+            return Err(Error::basic(error_message));
+        }
 
-        // TODO Not reacting on failure
-        let request = medium.build_request(message);
-        let http_response = request.send()
-            .await
-            .map_err(|err| {
-                let error_message = format!("Could not send message to medium {}", medium.get_id());
-                Error::new(error_message, err)
-            })?;
-
-        let http_status = &http_response.status();
-        if http_status.is_client_error() || http_status.is_server_error() {
-            let status_err = Error::basic(format!("Expected HTTP OK, but received {} for medium {}", http_status, medium.get_id()));
-            Err(status_err)?;
-        }
-    
         Ok(())
-    }    
+    }
+
+}
+
+/// Build a medium from the settings of an alerter
+type MediumBuilder = fn(&AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error>;
+
+/// Supported mediums, with the alerter settings accepted by each medium (the name, the
+/// medium and the recovery flag apply to all mediums) and the medium builder
+const MEDIUMS: [(&str, &[&str], MediumBuilder); 9] = [
+    ("telegram", &["chat_env", "token_env"], build_telegram),
+    ("spryng", &["recipients_env", "token_env"], build_spryng),
+    ("slack", &["url_env"], build_slack),
+    ("discord", &["url_env"], build_discord),
+    ("mattermost", &["url_env"], build_mattermost),
+    ("pagerduty", &["token_env", "url"], build_pagerduty),
+    ("opsgenie", &["token_env", "url"], build_opsgenie),
+    ("webhook", &["url_env", "method", "headers", "body", "secret_env"], build_webhook),
+    ("smtp", &["host", "port", "starttls", "username_env", "password_env", "from", "to", "subject"], build_smtp)
+];
+
+/// Mediums resolving their alert with the recovery message (the alert would otherwise
+/// never be closed), recovery messages can not be disabled on these mediums
+const RECOVERY_MEDIUMS: [&str; 2] = ["pagerduty", "opsgenie"];
+
+pub fn requires_recovery(medium: &str) -> bool {
+    RECOVERY_MEDIUMS.contains(&medium)
+}
+
+/// Alerter settings accepted by a medium, unknown mediums are reported when the
+/// alert manager is built
+pub fn medium_settings(medium: &str) -> Option<&'static [&'static str]> {
+
+    MEDIUMS.iter()
+        .find(|(medium_name, _, _)| *medium_name == medium)
+        .map(|(_, settings, _)| *settings)
+}
+
+fn build_telegram(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    let chat_env = alerter.chat_env.clone().ok_or(Error::basic("Expected 'chat_env' configuration with Telegram medium"))?;
+    let token_env = alerter.token_env.clone().ok_or(Error::basic("Expected 'token_env' configuration with Telegram medium"))?;
+
+    let telegram_chat = env::var(chat_env).map_err(|_| Error::basic("Expected Telegram chat ID as environment variable"))?;
+    let telegram_token = env::var(token_env).map_err(|_| Error::basic("Expected Telegram token as environment variable"))?;
+
+    Ok(Box::new(TelegramAlerter::new(&alerter.name, &telegram_chat, &telegram_token)))
+}
+
+fn build_spryng(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    let recipients_env = alerter.recipients_env.clone().ok_or(Error::basic("Expected 'recipients_env' configuration with Spryng medium"))?;
+    let token_env = alerter.token_env.clone().ok_or(Error::basic("Expected 'token_env' configuration with Spryng medium"))?;
+
+    let spring_recipients = env::var(recipients_env).map_err(|_| Error::basic("Expected Spryng SMS recipients as environment variable"))?;
+    let spryng_token = env::var(token_env).map_err(|_| Error::basic("Expected Spryng token as environment variable"))?;
+
+    let formatted_recipients: Vec<String> = spring_recipients.split(',')
+        .map(|recipient| recipient.trim().to_string())
+        .collect();
+
+    Ok(Box::new(SpryngAlerter::new(&alerter.name, &spryng_token, formatted_recipients)))
+}
+
+/// Incoming webhook URL of the chat mediums (Slack, Discord and Mattermost)
+fn chat_webhook_url(alerter: &AlertConfig) -> Result<String, Error> {
+
+    let url_env = alerter.url_env.clone().ok_or_else(|| Error::basic(format!("Expected 'url_env' configuration with {} medium", alerter.medium)))?;
+    env::var(url_env).map_err(|_| Error::basic(format!("Expected {} webhook URL as environment variable", alerter.medium)))
+}
+
+fn build_slack(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    Ok(Box::new(SlackAlerter::new(&alerter.name, &chat_webhook_url(alerter)?)))
+}
+
+fn build_discord(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    Ok(Box::new(DiscordAlerter::new(&alerter.name, &chat_webhook_url(alerter)?)))
+}
+
+fn build_mattermost(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    Ok(Box::new(MattermostAlerter::new(&alerter.name, &chat_webhook_url(alerter)?)))
+}
+
+fn build_pagerduty(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    let token_env = alerter.token_env.clone().ok_or(Error::basic("Expected 'token_env' configuration with PagerDuty medium"))?;
+    let routing_key = env::var(token_env).map_err(|_| Error::basic("Expected PagerDuty routing key as environment variable"))?;
+    let events_url = alerter.url.clone().unwrap_or_else(|| DEFAULT_PAGERDUTY_URL.to_string());
+
+    Ok(Box::new(PagerDutyAlerter::new(&alerter.name, &events_url, &routing_key)))
+}
+
+fn build_opsgenie(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    let token_env = alerter.token_env.clone().ok_or(Error::basic("Expected 'token_env' configuration with Opsgenie medium"))?;
+    let api_key = env::var(token_env).map_err(|_| Error::basic("Expected Opsgenie API key as environment variable"))?;
+    let api_url = alerter.url.clone().unwrap_or_else(|| DEFAULT_OPSGENIE_URL.to_string());
+
+    Ok(Box::new(OpsgenieAlerter::new(&alerter.name, &api_url, &api_key)))
+}
+
+fn build_webhook(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    let url_env = alerter.url_env.clone().ok_or(Error::basic("Expected 'url_env' configuration with webhook medium"))?;
+    let url = env::var(url_env).map_err(|_| Error::basic("Expected webhook URL as environment variable"))?;
+    let method = alerter.method.clone().unwrap_or_else(|| "POST".to_string());
+
+    // The signing secret is optional, unsigned webhooks are sent without signature header
+    let secret = match &alerter.secret_env {
+        Some(secret_env) => Some(env::var(secret_env).map_err(|_| Error::basic("Expected webhook secret as environment variable"))?),
+        None => None
+    };
+
+    Ok(Box::new(WebhookAlerter::try_new(&alerter.name, &url, &method, &alerter.headers, alerter.body.clone(), secret)?))
+}
+
+fn build_smtp(alerter: &AlertConfig) -> Result<Box<dyn AlertMedium + Send + Sync>, Error> {
+
+    let host = alerter.host.clone().ok_or(Error::basic("Expected 'host' configuration with SMTP medium"))?;
+    let from = alerter.from.clone().ok_or(Error::basic("Expected 'from' configuration with SMTP medium"))?;
+
+    let credentials = match (&alerter.username_env, &alerter.password_env) {
+        (Some(username_env), Some(password_env)) => {
+            let username = env::var(username_env).map_err(|_| Error::basic("Expected SMTP username as environment variable"))?;
+            let password = env::var(password_env).map_err(|_| Error::basic("Expected SMTP password as environment variable"))?;
+            Some((username, password))
+        },
+        (None, None) => None,
+        _ => Err(Error::basic("Expected both 'username_env' and 'password_env' configuration with SMTP medium"))?
+    };
+
+    let smtp = SmtpAlerter::try_new(&alerter.name, SmtpSettings {
+        host,
+        port: alerter.port.unwrap_or(DEFAULT_SMTP_PORT),
+        starttls: alerter.starttls,
+        credentials,
+        from,
+        to: alerter.to.clone(),
+        subject: alerter.subject.clone().unwrap_or_else(|| DEFAULT_SMTP_SUBJECT.to_string())
+    })?;
+
+    Ok(Box::new(smtp))
+}
+
+/// HTTP client of a medium, built once and reused for all the alerts of the medium
+pub fn build_http_client() -> Client {
+
+    Client::builder()
+        .timeout(REQUEST_TIMEOUT)
+        .build()
+        .expect("Expecting a valid HTTP client configuration")
+}
+
+/// Send the HTTP request of a medium, a client or server error status is
+/// considered as a failure
+pub async fn send_request(medium_id: &str, request: RequestBuilder) -> Result<(), Error> {
+
+    let http_response = request.send()
+        .await
+        .map_err(|err| {
+            let error_message = format!("Could not send message to medium {}", medium_id);
+            Error::new(error_message, err)
+        })?;
+
+    let http_status = &http_response.status();
+    if http_status.is_client_error() || http_status.is_server_error() {
+        let status_err = Error::basic(format!("Expected HTTP OK, but received {} for medium {}", http_status, medium_id));
+        Err(status_err)?;
+    }
+
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    struct DummyMedium {
+        id: String
+    }
+
+    #[async_trait]
+    impl AlertMedium for DummyMedium {
+
+        fn get_id(&self) -> String {
+            self.id.clone()
+        }
+
+        async fn send(&self, _alert: &Alert) -> Result<(), Error> {
+            Ok(())
+        }
+
+    }
+
+    fn build_route(region: Option<&str>, group: Option<&str>, severity: Option<AlertSeverity>, alerters: Vec<&str>) -> AlertRouteConfig {
+
+        AlertRouteConfig {
+            region: region.map(String::from),
+            group: group.map(String::from),
+            severity,
+            alerters: alerters.into_iter().map(String::from).collect()
+        }
+    }
+
+    fn build_manager(routes: Vec<AlertRouteConfig>) -> AlertManager {
+
+        let mut manager = AlertManager::try_from_config(&[], &routes).unwrap();
+        for medium_id in ["telegram", "sms", "webhook"] {
+            manager.add_medium(DummyMedium { id: medium_id.to_string() });
+        }
+        manager
+    }
+
+    #[test]
+    fn should_alert_all_mediums_without_routes() {
+
+        let manager = build_manager(vec![]);
+        let alert = Alert::incident("region-north", None, AlertSeverity::Warning, "Region region-north is DOWN");
+
+        assert_eq!(manager.route_mediums(&alert), vec!["sms", "telegram", "webhook"]);
+    }
+
+    #[test]
+    fn should_route_alerts_by_scope() {
+
+        let manager = build_manager(vec![
+            build_route(None, None, None, vec!["telegram"]),
+            build_route(None, None, Some(AlertSeverity::Critical), vec!["sms", "telegram"]),
+            build_route(Some("region-south"), Some("database"), None, vec!["webhook"])
+        ]);
+
+        let group_warning = Alert::incident("region-north", Some("default"), AlertSeverity::Warning, "Group region-north.default is DOWN");
+        assert_eq!(manager.route_mediums(&group_warning), vec!["telegram"]);
+
+        let region_critical = Alert::incident("region-north", None, AlertSeverity::Critical, "Region region-north is DOWN");
+        assert_eq!(manager.route_mediums(&region_critical), vec!["telegram", "sms"]);
+
+        let database_warning = Alert::incident("region-south", Some("database"), AlertSeverity::Warning, "Group region-south.database is DOWN");
+        assert_eq!(manager.route_mediums(&database_warning), vec!["telegram", "webhook"]);
+
+        // Group routes do not receive the alerts of the whole region
+        let region_south = Alert::incident("region-south", None, AlertSeverity::Warning, "Region region-south is DOWN");
+        assert_eq!(manager.route_mediums(&region_south), vec!["telegram"]);
+    }
 
 }
@@ -0,0 +1,83 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::common::error::Error;
+
+use super::chat::CHAT_USERNAME;
+use super::manager::{build_http_client, send_request, Alert, AlertMedium};
+use super::slack::build_attachment;
+
+/// Mattermost incoming webhook, Mattermost accepts the Slack message attachments
+pub struct MattermostAlerter {
+
+    id: String,
+    webhook_url: String,
+    http_client: Client
+
+}
+
+impl MattermostAlerter {
+
+    pub fn new<M>(id: M, webhook_url: M) -> Self where M: Into<String> {
+
+        MattermostAlerter {
+            id: id.into(),
+            webhook_url: webhook_url.into(),
+            http_client: build_http_client()
+        }
+    }
+
+}
+
+fn build_message(alert: &Alert) -> Value {
+
+    json!({
+        "username": CHAT_USERNAME,
+        "attachments": [build_attachment(alert)]
+    })
+}
+
+#[async_trait]
+impl AlertMedium for MattermostAlerter {
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+
+        let request = self.http_client
+            .post(&self.webhook_url)
+            .json(&build_message(alert));
+
+        send_request(&self.id, request).await
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::server::config::AlertSeverity;
+
+    use super::*;
+
+    #[test]
+    fn should_build_mattermost_message() {
+
+        let alert = Alert::incident("region-north", None, AlertSeverity::Warning, "Region region-north is DOWN");
+        let message = build_message(&alert);
+
+        assert_eq!(message["username"], CHAT_USERNAME);
+
+        let attachment = &message["attachments"][0];
+        assert_eq!(attachment["color"], "#E8A317");
+        assert_eq!(attachment["title"], "Region region-north is DOWN");
+        assert_eq!(attachment["fields"], json!([
+            { "title": "Region", "value": "region-north", "short": true },
+            { "title": "Severity", "value": "warning", "short": true }
+        ]));
+    }
+
+}
@@ -1,4 +1,12 @@
 pub mod manager;
 
+mod chat;
+mod discord;
+mod mattermost;
+mod opsgenie;
+mod pagerduty;
+mod slack;
+mod smtp;
 mod spryng;
 mod telegram;
+mod webhook;
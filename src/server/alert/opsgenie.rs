@@ -0,0 +1,133 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::common::error::Error;
+use crate::server::config::AlertSeverity;
+
+use super::chat::alert_fields;
+use super::manager::{build_http_client, send_request, Alert, AlertKind, AlertMedium};
+
+/// Opsgenie API (use 'https://api.eu.opsgenie.com' for accounts hosted in Europe)
+pub const DEFAULT_OPSGENIE_URL: &str = "https://api.opsgenie.com";
+
+/// Opsgenie rejects alert messages longer than 130 characters
+const MAX_MESSAGE_LENGTH: usize = 130;
+
+const ALERT_SOURCE: &str = "watchdog";
+
+/**
+ * Opsgenie Alert API medium, incidents create an Opsgenie alert and recoveries close
+ * it. The alert alias is the deduplication key of the region or the group.
+ */
+pub struct OpsgenieAlerter {
+
+    id: String,
+    base_url: String,
+    api_key: String,
+    http_client: Client
+
+}
+
+impl OpsgenieAlerter {
+
+    pub fn new<M>(id: M, base_url: M, api_key: M) -> Self where M: Into<String> {
+
+        OpsgenieAlerter {
+            id: id.into(),
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            api_key: api_key.into(),
+            http_client: build_http_client()
+        }
+    }
+
+    fn build_alert(&self, alert: &Alert) -> Value {
+
+        let priority = match (alert.kind, alert.severity) {
+            (AlertKind::Test, _) => "P5",
+            (_, AlertSeverity::Warning) => "P3",
+            (_, AlertSeverity::Critical) => "P1"
+        };
+
+        let details: serde_json::Map<String, Value> = alert_fields(alert).into_iter()
+            .map(|(name, value)| (name.to_lowercase(), Value::from(value)))
+            .collect();
+
+        json!({
+            "message": alert.message.chars().take(MAX_MESSAGE_LENGTH).collect::<String>(),
+            "alias": alert.dedup_key(),
+            "description": alert.message,
+            "priority": priority,
+            "source": ALERT_SOURCE,
+            "tags": ["watchdog"],
+            "details": details
+        })
+    }
+
+    async fn create_alert(&self, alert: &Alert) -> Result<(), Error> {
+
+        let request = self.http_client
+            .post(format!("{}/v2/alerts", self.base_url))
+            .header("Authorization", format!("GenieKey {}", self.api_key))
+            .json(&self.build_alert(alert));
+
+        send_request(&self.id, request).await
+    }
+
+    async fn close_alert(&self, alert: &Alert) -> Result<(), Error> {
+
+        let request = self.http_client
+            .post(format!("{}/v2/alerts/{}/close", self.base_url, alert.dedup_key()))
+            .query(&[("identifierType", "alias")])
+            .header("Authorization", format!("GenieKey {}", self.api_key))
+            .json(&json!({
+                "source": ALERT_SOURCE,
+                "note": alert.message
+            }));
+
+        send_request(&self.id, request).await
+    }
+
+}
+
+#[async_trait]
+impl AlertMedium for OpsgenieAlerter {
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+
+        match alert.kind {
+            AlertKind::Incident => self.create_alert(alert).await,
+            AlertKind::Recovery => self.close_alert(alert).await,
+            AlertKind::Test => {
+                self.create_alert(alert).await?;
+                self.close_alert(alert).await
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_build_alert() {
+
+        let alerter = OpsgenieAlerter::new("opsgenie", "https://api.eu.opsgenie.com/", "api-key");
+        assert_eq!(alerter.base_url, "https://api.eu.opsgenie.com");
+
+        let incident = Alert::incident("region-north", None, AlertSeverity::Critical, "Region region-north is DOWN");
+        let body = alerter.build_alert(&incident);
+
+        assert_eq!(body["alias"], "watchdog/region-north");
+        assert_eq!(body["priority"], "P1");
+        assert_eq!(body["details"]["region"], "region-north");
+    }
+
+}
@@ -0,0 +1,141 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::common::error::Error;
+use crate::server::config::AlertSeverity;
+
+use super::chat::alert_fields;
+use super::manager::{build_http_client, send_request, Alert, AlertKind, AlertMedium};
+
+pub const DEFAULT_PAGERDUTY_URL: &str = "https://events.pagerduty.com/v2/enqueue";
+
+/**
+ * PagerDuty Events API v2 medium, incidents trigger a PagerDuty alert and recoveries
+ * resolve it. Both events share the deduplication key of the region or the group, the
+ * PagerDuty alert therefore follows the watchdog incident.
+ */
+pub struct PagerDutyAlerter {
+
+    id: String,
+    url: String,
+    routing_key: String,
+    http_client: Client
+
+}
+
+impl PagerDutyAlerter {
+
+    pub fn new<M>(id: M, url: M, routing_key: M) -> Self where M: Into<String> {
+
+        PagerDutyAlerter {
+            id: id.into(),
+            url: url.into(),
+            routing_key: routing_key.into(),
+            http_client: build_http_client()
+        }
+    }
+
+    fn build_event(&self, alert: &Alert, event_action: &str) -> Value {
+
+        if event_action == "resolve" {
+            return json!({
+                "routing_key": self.routing_key,
+                "event_action": event_action,
+                "dedup_key": alert.dedup_key()
+            });
+        }
+
+        let severity = match (alert.kind, alert.severity) {
+            (AlertKind::Test, _) => "info",
+            (_, AlertSeverity::Warning) => "warning",
+            (_, AlertSeverity::Critical) => "critical"
+        };
+
+        let custom_details: serde_json::Map<String, Value> = alert_fields(alert).into_iter()
+            .map(|(name, value)| (name.to_lowercase(), Value::from(value)))
+            .collect();
+
+        let mut payload = json!({
+            "summary": alert.message,
+            "source": if alert.region.is_empty() { "watchdog" } else { &alert.region },
+            "severity": severity,
+            "timestamp": alert.timestamp.to_rfc3339(),
+            "custom_details": custom_details
+        });
+        if let Some(group) = &alert.group {
+            payload["component"] = Value::from(group.as_str());
+        }
+
+        json!({
+            "routing_key": self.routing_key,
+            "event_action": event_action,
+            "dedup_key": alert.dedup_key(),
+            "payload": payload
+        })
+    }
+
+    async fn send_event(&self, alert: &Alert, event_action: &str) -> Result<(), Error> {
+
+        let request = self.http_client
+            .post(&self.url)
+            .json(&self.build_event(alert, event_action));
+
+        send_request(&self.id, request).await
+    }
+
+}
+
+#[async_trait]
+impl AlertMedium for PagerDutyAlerter {
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+
+        match alert.kind {
+            AlertKind::Incident => self.send_event(alert, "trigger").await,
+            AlertKind::Recovery => self.send_event(alert, "resolve").await,
+            AlertKind::Test => {
+                // Resolved right away, a test message must not keep anyone paged
+                self.send_event(alert, "trigger").await?;
+                self.send_event(alert, "resolve").await
+            }
+        }
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_build_trigger_and_resolve_events() {
+
+        let alerter = PagerDutyAlerter::new("pagerduty", DEFAULT_PAGERDUTY_URL, "routing-key");
+
+        let incident = Alert::incident("region-north", Some("database"), AlertSeverity::Warning, "Group region-north.database is DOWN");
+        let trigger = alerter.build_event(&incident, "trigger");
+
+        assert_eq!(trigger["event_action"], "trigger");
+        assert_eq!(trigger["dedup_key"], "watchdog/region-north/database");
+        assert_eq!(trigger["payload"]["summary"], "Group region-north.database is DOWN");
+        assert_eq!(trigger["payload"]["source"], "region-north");
+        assert_eq!(trigger["payload"]["component"], "database");
+        assert_eq!(trigger["payload"]["severity"], "warning");
+
+        let recovery = Alert::recovery("region-north", Some("database"), AlertSeverity::Warning, "Group region-north.database is UP again", 60);
+        let resolve = alerter.build_event(&recovery, "resolve");
+
+        assert_eq!(resolve, json!({
+            "routing_key": "routing-key",
+            "event_action": "resolve",
+            "dedup_key": "watchdog/region-north/database"
+        }));
+    }
+
+}
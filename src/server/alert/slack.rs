@@ -0,0 +1,98 @@
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::{json, Value};
+
+use crate::common::error::Error;
+
+use super::chat::{alert_color, alert_fields, hex_color};
+use super::manager::{build_http_client, send_request, Alert, AlertMedium};
+
+/// Slack incoming webhook, the alert is sent as a colored attachment
+pub struct SlackAlerter {
+
+    id: String,
+    webhook_url: String,
+    http_client: Client
+
+}
+
+impl SlackAlerter {
+
+    pub fn new<M>(id: M, webhook_url: M) -> Self where M: Into<String> {
+
+        SlackAlerter {
+            id: id.into(),
+            webhook_url: webhook_url.into(),
+            http_client: build_http_client()
+        }
+    }
+
+}
+
+/// Slack-compatible attachment, also accepted by Mattermost
+pub fn build_attachment(alert: &Alert) -> Value {
+
+    let fields: Vec<Value> = alert_fields(alert).into_iter()
+        .map(|(name, value)| json!({ "title": name, "value": value, "short": true }))
+        .collect();
+
+    json!({
+        "fallback": alert.message,
+        "color": hex_color(alert_color(alert)),
+        "title": alert.message,
+        "fields": fields,
+        "ts": alert.timestamp.timestamp()
+    })
+}
+
+fn build_message(alert: &Alert) -> Value {
+
+    json!({
+        "attachments": [build_attachment(alert)]
+    })
+}
+
+#[async_trait]
+impl AlertMedium for SlackAlerter {
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+
+        let request = self.http_client
+            .post(&self.webhook_url)
+            .json(&build_message(alert));
+
+        send_request(&self.id, request).await
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use crate::server::config::AlertSeverity;
+
+    use super::*;
+
+    #[test]
+    fn should_build_slack_message() {
+
+        let alert = Alert::incident("region-north", Some("default"), AlertSeverity::Critical, "Group region-north.default is DOWN");
+        let message = build_message(&alert);
+
+        let attachment = &message["attachments"][0];
+        assert_eq!(attachment["color"], "#D63232");
+        assert_eq!(attachment["title"], "Group region-north.default is DOWN");
+        assert_eq!(attachment["fallback"], "Group region-north.default is DOWN");
+        assert_eq!(attachment["ts"], alert.timestamp.timestamp());
+        assert_eq!(attachment["fields"], json!([
+            { "title": "Region", "value": "region-north", "short": true },
+            { "title": "Group", "value": "default", "short": true },
+            { "title": "Severity", "value": "critical", "short": true }
+        ]));
+    }
+
+}
@@ -0,0 +1,425 @@
+use std::{convert::TryFrom, sync::Arc, time::Duration};
+
+use async_trait::async_trait;
+use base64::{Engine, engine::general_purpose::STANDARD};
+use tokio::{io::{AsyncBufReadExt, AsyncRead, AsyncWrite, AsyncWriteExt, BufReader}, net::TcpStream, time::timeout};
+use tokio_rustls::TlsConnector;
+use tokio_rustls::rustls::{ClientConfig, OwnedTrustAnchor, RootCertStore, ServerName};
+
+use crate::common::{error::Error, utils::format_duration};
+
+use super::manager::{Alert, AlertKind, AlertMedium};
+
+pub const DEFAULT_SMTP_PORT: u16 = 587;
+pub const DEFAULT_SMTP_SUBJECT: &str = "[watchdog] {{message}}";
+
+/// Maximum duration of a whole SMTP exchange (connection, handshake and message delivery)
+const SMTP_TIMEOUT_MS: u64 = 30000;
+
+/// Domain announced in the EHLO command
+const EHLO_DOMAIN: &str = "localhost";
+
+pub struct SmtpSettings {
+    pub host: String,
+    pub port: u16,
+    pub starttls: bool,
+    /// Username and password, sent with the AUTH PLAIN mechanism
+    pub credentials: Option<(String, String)>,
+    pub from: String,
+    pub to: Vec<String>,
+    /// Subject template, with the same placeholders as the webhook body
+    pub subject: String
+}
+
+/**
+ * Email medium, the alerts are sent as plain text emails to an SMTP server (such as
+ * a mail submission server on port 587). The connection is upgraded with STARTTLS
+ * unless disabled, credentials are only sent over an encrypted connection.
+ */
+pub struct SmtpAlerter {
+
+    id: String,
+    settings: SmtpSettings,
+    connector: TlsConnector
+
+}
+
+impl SmtpAlerter {
+
+    pub fn try_new(id: &str, settings: SmtpSettings) -> Result<Self, Error> {
+
+        if settings.to.is_empty() {
+            return Err(Error::basic("Expected at least one 'to' address with SMTP medium"));
+        }
+        for address in std::iter::once(&settings.from).chain(settings.to.iter()) {
+            validate_address(address)?;
+        }
+
+        if settings.credentials.is_some() && !settings.starttls {
+            return Err(Error::basic("SMTP credentials can only be sent with STARTTLS enabled"));
+        }
+
+        // Same Mozilla root certificates as the TLS tests and the HTTP client
+        let mut root_store = RootCertStore::empty();
+        root_store.add_server_trust_anchors(webpki_roots::TLS_SERVER_ROOTS.0.iter().map(|trust_anchor| {
+            OwnedTrustAnchor::from_subject_spki_name_constraints(
+                trust_anchor.subject,
+                trust_anchor.spki,
+                trust_anchor.name_constraints
+            )
+        }));
+
+        let client_config = ClientConfig::builder()
+            .with_safe_defaults()
+            .with_root_certificates(root_store)
+            .with_no_client_auth();
+
+        Ok(SmtpAlerter {
+            id: id.to_string(),
+            settings,
+            connector: TlsConnector::from(Arc::new(client_config))
+        })
+    }
+
+    async fn deliver(&self, message: &str) -> Result<(), Error> {
+
+        let tcp_stream = TcpStream::connect((self.settings.host.as_str(), self.settings.port)).await
+            .map_err(|err| Error::new(format!("Could not connect to SMTP server {}:{}", self.settings.host, self.settings.port), err))?;
+
+        let mut session = SmtpSession::new(tcp_stream);
+        session.expect_reply("greeting", 220).await?;
+        let capabilities = session.command(&format!("EHLO {}", EHLO_DOMAIN), 250).await?;
+
+        if !self.settings.starttls {
+            return self.send_message(session, message).await;
+        }
+
+        if !capabilities.lines().any(|capability| capability.trim().eq_ignore_ascii_case("STARTTLS")) {
+            return Err(Error::basic(format!("SMTP server {} does not support STARTTLS", self.settings.host)));
+        }
+        session.command("STARTTLS", 220).await?;
+
+        let server_name = ServerName::try_from(self.settings.host.as_str())
+            .map_err(|err| Error::new(format!("Invalid SMTP host {}", self.settings.host), err))?;
+        let tls_stream = self.connector.connect(server_name, session.into_inner()).await
+            .map_err(|err| Error::new(format!("Could not establish TLS with SMTP server {}", self.settings.host), err))?;
+
+        // Capabilities must be requested again once the connection is encrypted
+        let mut session = SmtpSession::new(tls_stream);
+        session.command(&format!("EHLO {}", EHLO_DOMAIN), 250).await?;
+
+        self.send_message(session, message).await
+    }
+
+    async fn send_message<S>(&self, mut session: SmtpSession<S>, message: &str) -> Result<(), Error> where S: AsyncRead + AsyncWrite + Unpin {
+
+        if let Some((username, password)) = &self.settings.credentials {
+            let token = STANDARD.encode(format!("\0{}\0{}", username, password));
+            session.command(&format!("AUTH PLAIN {}", token), 235).await?;
+        }
+
+        session.command(&format!("MAIL FROM:<{}>", self.settings.from), 250).await?;
+        for recipient in self.settings.to.iter() {
+            session.command(&format!("RCPT TO:<{}>", recipient), 250).await?;
+        }
+
+        session.command("DATA", 354).await?;
+        session.command(&format!("{}\r\n.", message), 250).await?;
+
+        // The message is already accepted, a failing QUIT is not an error
+        let _ = session.command("QUIT", 221).await;
+
+        Ok(())
+    }
+
+    /// Format the alert as a plain text email (headers and body), the body is
+    /// base64-encoded when it contains non-ASCII characters
+    fn format_message(&self, alert: &Alert) -> String {
+
+        let subject = alert.render(&self.settings.subject).replace(['\r', '\n'], " ");
+        let encoded_subject = if subject.is_ascii() {
+            subject
+        } else {
+            format!("=?UTF-8?B?{}?=", STANDARD.encode(subject))
+        };
+
+        let mut body_lines: Vec<String> = vec![alert.message.clone(), String::new()];
+        if !alert.region.is_empty() {
+            body_lines.push(format!("Region: {}", alert.region));
+        }
+        if let Some(group) = &alert.group {
+            body_lines.push(format!("Group: {}", group));
+        }
+        if alert.kind != AlertKind::Test {
+            body_lines.push(format!("Severity: {}", alert.field_value("severity").as_str().unwrap_or_default()));
+        }
+        body_lines.push(format!("Time: {}", alert.timestamp.to_rfc2822()));
+        if let Some(duration_secs) = alert.duration_secs {
+            body_lines.push(format!("Outage duration: {}", format_duration(duration_secs)));
+        }
+        let body = body_lines.join("\n");
+
+        let (transfer_encoding, encoded_body) = if body.is_ascii() {
+            ("7bit", dot_stuff(&body))
+        } else {
+            ("base64", wrap_lines(&STANDARD.encode(body), 76))
+        };
+
+        let headers = [
+            format!("From: {}", self.settings.from),
+            format!("To: {}", self.settings.to.join(", ")),
+            format!("Subject: {}", encoded_subject),
+            format!("Date: {}", alert.timestamp.to_rfc2822()),
+            "MIME-Version: 1.0".to_string(),
+            "Content-Type: text/plain; charset=utf-8".to_string(),
+            format!("Content-Transfer-Encoding: {}", transfer_encoding)
+        ];
+
+        format!("{}\r\n\r\n{}", headers.join("\r\n"), encoded_body)
+    }
+
+}
+
+#[async_trait]
+impl AlertMedium for SmtpAlerter {
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+
+        let message = self.format_message(alert);
+
+        timeout(Duration::from_millis(SMTP_TIMEOUT_MS), self.deliver(&message)).await
+            .map_err(|_elapsed| Error::basic(format!("SMTP server timed out for medium {}", self.id)))?
+    }
+
+}
+
+/// SMTP command/reply exchange over a plain or encrypted stream
+struct SmtpSession<S> {
+    stream: BufReader<S>
+}
+
+impl<S> SmtpSession<S> where S: AsyncRead + AsyncWrite + Unpin {
+
+    fn new(stream: S) -> Self {
+
+        SmtpSession {
+            stream: BufReader::new(stream)
+        }
+    }
+
+    fn into_inner(self) -> S {
+        self.stream.into_inner()
+    }
+
+    /// Send a command and wait for a reply of the same class as the expected
+    /// code (any 2xx reply is accepted for an expected 250)
+    async fn command(&mut self, command: &str, expected_code: u16) -> Result<String, Error> {
+
+        // Only the verb is reported in errors, the arguments may contain credentials
+        let verb = command.split_whitespace().next().unwrap_or_default();
+
+        let line = format!("{}\r\n", command);
+        self.stream.get_mut().write_all(line.as_bytes()).await
+            .map_err(|err| Error::new(format!("Could not send SMTP command {}", verb), err))?;
+        self.stream.get_mut().flush().await
+            .map_err(|err| Error::new(format!("Could not send SMTP command {}", verb), err))?;
+
+        self.expect_reply(verb, expected_code).await
+    }
+
+    /// Read a reply (possibly on multiple lines) and return its text, one line per
+    /// reply line
+    async fn expect_reply(&mut self, step: &str, expected_code: u16) -> Result<String, Error> {
+
+        let mut reply_lines: Vec<String> = vec![];
+        loop {
+
+            let mut line = String::new();
+            let read_count = self.stream.read_line(&mut line).await
+                .map_err(|err| Error::new(format!("Could not read SMTP reply to {}", step), err))?;
+
+            if read_count == 0 {
+                return Err(Error::new(format!("Could not read SMTP reply to {}", step), "Connection closed by the server"));
+            }
+
+            let line = line.trim_end();
+            let reply_code = line.get(..3).and_then(|code| code.parse::<u16>().ok())
+                .ok_or_else(|| Error::new(format!("Invalid SMTP reply to {}", step), line.to_string()))?;
+
+            reply_lines.push(line.get(4..).unwrap_or_default().to_string());
+
+            // Multi-line replies use a dash after the code on all lines except the last one
+            if line.as_bytes().get(3) == Some(&b'-') {
+                continue;
+            }
+
+            if reply_code / 100 != expected_code / 100 {
+                return Err(Error::new(format!("Unexpected SMTP reply to {}", step), format!("{} {}", reply_code, reply_lines.join(" "))));
+            }
+
+            return Ok(reply_lines.join("\n"));
+        }
+    }
+
+}
+
+fn validate_address(address: &str) -> Result<(), Error> {
+
+    let is_valid = address.contains('@') && !address.contains(|character: char| character.is_whitespace() || character == '<' || character == '>');
+    if !is_valid {
+        return Err(Error::basic(format!("Invalid email address '{}' with SMTP medium", address)));
+    }
+
+    Ok(())
+}
+
+/// Convert the line endings to CRLF and escape the lines starting with a dot (a
+/// single dot on a line ends the message)
+fn dot_stuff(body: &str) -> String {
+
+    body.lines()
+        .map(|line| if line.starts_with('.') { format!(".{}", line) } else { line.to_string() })
+        .collect::<Vec<String>>()
+        .join("\r\n")
+}
+
+fn wrap_lines(text: &str, width: usize) -> String {
+
+    text.as_bytes()
+        .chunks(width)
+        .map(|chunk| String::from_utf8_lossy(chunk).into_owned())
+        .collect::<Vec<String>>()
+        .join("\r\n")
+}
+
+#[cfg(test)]
+mod tests {
+
+    use tokio::net::TcpListener;
+
+    use crate::server::config::AlertSeverity;
+
+    use super::*;
+
+    fn build_settings(port: u16) -> SmtpSettings {
+
+        SmtpSettings {
+            host: "127.0.0.1".to_string(),
+            port,
+            starttls: false,
+            credentials: None,
+            from: "watchdog@example.org".to_string(),
+            to: vec!["oncall@example.org".to_string(), "noc@example.org".to_string()],
+            subject: "[{{severity}}] {{message}}".to_string()
+        }
+    }
+
+    /// Launch a local SMTP stub accepting a single message, and return the port
+    /// it is listening on with the commands and message data it received
+    async fn launch_smtp_stub() -> (u16, tokio::task::JoinHandle<Vec<String>>) {
+
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let port = listener.local_addr().unwrap().port();
+
+        let stub = tokio::spawn(async move {
+
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut stream = BufReader::new(stream);
+            stream.get_mut().write_all(b"220 stub ESMTP\r\n").await.unwrap();
+
+            let mut received_lines: Vec<String> = vec![];
+            let mut in_data = false;
+            loop {
+
+                let mut line = String::new();
+                if stream.read_line(&mut line).await.unwrap() == 0 {
+                    break;
+                }
+                let line = line.trim_end().to_string();
+                received_lines.push(line.clone());
+
+                if in_data && line != "." {
+                    continue;
+                }
+
+                let reply: &[u8] = match line.as_str() {
+                    "." => { in_data = false; b"250 queued\r\n" },
+                    "DATA" => { in_data = true; b"354 go ahead\r\n" },
+                    "QUIT" => b"221 bye\r\n",
+                    command if command.starts_with("EHLO") => b"250-stub\r\n250 8BITMIME\r\n",
+                    _ => b"250 ok\r\n"
+                };
+                stream.get_mut().write_all(reply).await.unwrap();
+            }
+
+            received_lines
+        });
+
+        (port, stub)
+    }
+
+    #[tokio::test]
+    async fn should_send_alert_to_smtp_server() {
+
+        let (port, stub) = launch_smtp_stub().await;
+        let alerter = SmtpAlerter::try_new("email", build_settings(port)).unwrap();
+
+        let alert = Alert::recovery("region-north", Some("default"), AlertSeverity::Critical, "Group region-north.default is UP again", 185);
+        alerter.send(&alert).await.unwrap();
+
+        let received_lines = stub.await.unwrap();
+        assert_eq!(received_lines[0], "EHLO localhost");
+        assert_eq!(received_lines[1], "MAIL FROM:<watchdog@example.org>");
+        assert_eq!(received_lines[2], "RCPT TO:<oncall@example.org>");
+        assert_eq!(received_lines[3], "RCPT TO:<noc@example.org>");
+        assert!(received_lines.contains(&"Subject: [critical] Group region-north.default is UP again".to_string()));
+        assert!(received_lines.contains(&"Group: default".to_string()));
+        assert!(received_lines.contains(&"Outage duration: 3m 05s".to_string()));
+        assert_eq!(received_lines.last().unwrap(), "QUIT");
+    }
+
+    #[tokio::test]
+    async fn should_require_starttls_capability() {
+
+        let (port, _stub) = launch_smtp_stub().await;
+
+        let settings = SmtpSettings { starttls: true, ..build_settings(port) };
+        let alerter = SmtpAlerter::try_new("email", settings).unwrap();
+
+        let error = alerter.send(&Alert::test("This is a watchdog monitoring test message")).await.unwrap_err();
+        assert_eq!(error.message, "SMTP server 127.0.0.1 does not support STARTTLS");
+    }
+
+    #[test]
+    fn should_format_message() {
+
+        let alerter = SmtpAlerter::try_new("email", build_settings(DEFAULT_SMTP_PORT)).unwrap();
+
+        let alert = Alert::incident("région-nord", None, AlertSeverity::Critical, "Region région-nord is DOWN");
+        let message = alerter.format_message(&alert);
+
+        assert!(message.contains("Subject: =?UTF-8?B?"));
+        assert!(message.contains("Content-Transfer-Encoding: base64\r\n"));
+        assert!(message.contains("To: oncall@example.org, noc@example.org\r\n"));
+
+        let alert = Alert::incident("region-north", None, AlertSeverity::Critical, ".hidden line");
+        let message = alerter.format_message(&alert);
+
+        assert!(message.contains("\r\n\r\n..hidden line\r\n"));
+    }
+
+    #[test]
+    fn should_reject_invalid_settings() {
+
+        let settings = SmtpSettings { to: vec!["oncall".to_string()], ..build_settings(DEFAULT_SMTP_PORT) };
+        assert!(SmtpAlerter::try_new("email", settings).is_err());
+
+        let settings = SmtpSettings { credentials: Some(("watchdog".to_string(), "secret".to_string())), ..build_settings(DEFAULT_SMTP_PORT) };
+        assert!(SmtpAlerter::try_new("email", settings).is_err());
+    }
+
+}
@@ -1,7 +1,10 @@
-use reqwest::{Client, RequestBuilder};
+use async_trait::async_trait;
+use reqwest::Client;
 use serde_json::json;
 
-use super::manager::AlertMedium;
+use crate::common::error::Error;
+
+use super::manager::{build_http_client, send_request, Alert, AlertMedium};
 
 pub struct SpryngAlerter {
 
@@ -11,6 +14,7 @@ pub struct SpryngAlerter {
     default_originator: String,
     default_route: String,
     recipients: Vec<String>,
+    http_client: Client
 
 }
 
@@ -24,32 +28,36 @@ impl SpryngAlerter {
             default_encoding: "auto".into(),
             default_originator: "watchdog".into(),
             default_route: "business".into(),
-            recipients
+            recipients,
+            http_client: build_http_client()
         }
     }
 
 }
 
+#[async_trait]
 impl AlertMedium for SpryngAlerter {
 
     fn get_id(&self) -> String {
         self.id.clone()
     }
 
-    fn build_request(&self, message: &str) -> RequestBuilder {
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
 
-        Client::new()
+        let request = self.http_client
             .post("https://rest.spryngsms.com/v1/messages")
             .header("Accept", "application/json")
             .header("Authorization", format!("Bearer {}", self.token))
             .header("Content-Type", "application/json")
             .json(&json!({
-                "body": message,
+                "body": alert.message,
                 "encoding": self.default_encoding,
                 "originator": self.default_originator,
                 "recipients": self.recipients,
                 "route": self.default_route
-            }))
+            }));
+
+        send_request(&self.id, request).await
     }
 
 }
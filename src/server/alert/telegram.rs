@@ -1,12 +1,20 @@
-use reqwest::{Client, RequestBuilder};
+use async_trait::async_trait;
+use reqwest::Client;
+use serde_json::json;
 
-use super::manager::AlertMedium;
+use crate::common::error::Error;
+
+use super::manager::{build_http_client, send_request, Alert, AlertMedium};
+
+/// Characters that must be escaped in MarkdownV2 messages (including the backslash itself)
+const MARKDOWN_RESERVED_CHARS: &str = "_*[]()~`>#+-=|{}.!\\";
 
 pub struct TelegramAlerter {
 
     id: String,
     chat_id: String,
-    token: String
+    token: String,
+    http_client: Client
 
 }
 
@@ -17,30 +25,60 @@ impl TelegramAlerter {
         TelegramAlerter {
             id: id.into(),
             chat_id: chat_id.into(),
-            token: token.into()
+            token: token.into(),
+            http_client: build_http_client()
         }
     }
 
 }
 
+#[async_trait]
 impl AlertMedium for TelegramAlerter {
 
     fn get_id(&self) -> String {
         self.id.clone()
     }
 
-    fn build_request(&self, message: &str) -> RequestBuilder {
-        
-        let formatted_message = str::replace(message, "-", "\\-");
-    
-        let notify_route = format!("https://api.telegram.org/bot{}/sendMessage?chat_id={}&parse_mode=MarkdownV2&text={}", self.token, self.chat_id, formatted_message);
-    
-        /*if options.disable_notifications {
-            notify_route.push_str("&silent=true");
-        }*/
-    
-        let http_client = Client::new();
-        http_client.get(notify_route)
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+
+        let notify_route = format!("https://api.telegram.org/bot{}/sendMessage", self.token);
+        let message_body = json!({
+            "chat_id": self.chat_id,
+            "parse_mode": "MarkdownV2",
+            "text": escape_markdown(&alert.message)
+        });
+
+        send_request(&self.id, self.http_client.post(notify_route).json(&message_body)).await
+    }
+
+}
+
+/// Escape the characters reserved by the Telegram MarkdownV2 syntax, the message
+/// is displayed as plain text
+fn escape_markdown(text: &str) -> String {
+
+    let mut escaped_text = String::with_capacity(text.len());
+    for character in text.chars() {
+
+        if MARKDOWN_RESERVED_CHARS.contains(character) {
+            escaped_text.push('\\');
+        }
+        escaped_text.push(character);
+    }
+
+    escaped_text
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    #[test]
+    fn should_escape_markdown_characters() {
+
+        assert_eq!(escape_markdown("Group \"region-north.default\" is DOWN"), "Group \"region\\-north\\.default\" is DOWN");
+        assert_eq!(escape_markdown("_*[]()~`>#+-=|{}.!\\"), "\\_\\*\\[\\]\\(\\)\\~\\`\\>\\#\\+\\-\\=\\|\\{\\}\\.\\!\\\\");
     }
 
 }
@@ -0,0 +1,191 @@
+use std::collections::BTreeMap;
+
+use async_trait::async_trait;
+use reqwest::{Client, Method, header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE}};
+use ring::hmac;
+use serde_json::Value;
+
+use crate::common::error::Error;
+
+use super::manager::{build_http_client, send_request, Alert, AlertMedium, TEMPLATE_FIELDS};
+
+/// Header carrying the HMAC-SHA256 signature of the body (when a secret is configured)
+const SIGNATURE_HEADER: &str = "X-Watchdog-Signature";
+
+/**
+ * Generic HTTP medium, the alert is sent as a JSON body to the configured URL. The
+ * body can be customized with a JSON template: the {{region}}, {{group}}, {{severity}},
+ * {{kind}}, {{message}}, {{timestamp}} and {{duration}} placeholders are replaced in
+ * the template strings (a string only made of a placeholder keeps the type of the field,
+ * such as a number for the duration).
+ */
+pub struct WebhookAlerter {
+
+    id: String,
+    url: String,
+    method: Method,
+    headers: HeaderMap,
+    body_template: Option<Value>,
+    secret: Option<String>,
+    http_client: Client
+
+}
+
+impl WebhookAlerter {
+
+    pub fn try_new(id: &str, url: &str, method: &str, headers: &BTreeMap<String, String>, body_template: Option<Value>, secret: Option<String>) -> Result<Self, Error> {
+
+        let method = Method::from_bytes(method.to_uppercase().as_bytes())
+            .map_err(|err| Error::new(format!("Invalid webhook method '{}'", method), err))?;
+
+        let mut header_map = HeaderMap::new();
+        for (header_name, header_value) in headers.iter() {
+
+            let name = HeaderName::from_bytes(header_name.as_bytes())
+                .map_err(|err| Error::new(format!("Invalid webhook header name '{}'", header_name), err))?;
+            let value = HeaderValue::from_str(header_value)
+                .map_err(|err| Error::new(format!("Invalid webhook header value for '{}'", header_name), err))?;
+
+            header_map.insert(name, value);
+        }
+
+        Ok(WebhookAlerter {
+            id: id.to_string(),
+            url: url.to_string(),
+            method,
+            headers: header_map,
+            body_template,
+            secret,
+            http_client: build_http_client()
+        })
+    }
+
+    fn render_body(&self, alert: &Alert) -> Value {
+
+        match &self.body_template {
+            Some(template) => render_template(template, alert),
+            None => serde_json::to_value(alert).unwrap_or(Value::Null)
+        }
+    }
+
+}
+
+#[async_trait]
+impl AlertMedium for WebhookAlerter {
+
+    fn get_id(&self) -> String {
+        self.id.clone()
+    }
+
+    async fn send(&self, alert: &Alert) -> Result<(), Error> {
+
+        let body = self.render_body(alert).to_string();
+
+        let mut request = self.http_client.request(self.method.clone(), &self.url)
+            .header(CONTENT_TYPE, "application/json")
+            .headers(self.headers.clone());
+
+        if let Some(secret) = &self.secret {
+            request = request.header(SIGNATURE_HEADER, format!("sha256={}", sign_body(secret, body.as_bytes())));
+        }
+
+        send_request(&self.id, request.body(body)).await
+    }
+
+}
+
+/// Replace the placeholders in all string values of the template, object keys are kept
+/// as is (the rendered body is always valid JSON, whatever the alert message)
+fn render_template(template: &Value, alert: &Alert) -> Value {
+
+    match template {
+        Value::String(text) => {
+
+            let whole_placeholder = text.strip_prefix("{{")
+                .and_then(|text| text.strip_suffix("}}"))
+                .map(|placeholder| placeholder.trim())
+                .filter(|placeholder| TEMPLATE_FIELDS.contains(placeholder));
+
+            if let Some(placeholder) = whole_placeholder {
+                return alert.field_value(placeholder);
+            }
+
+            Value::String(alert.render(text))
+        },
+        Value::Array(items) => Value::Array(items.iter().map(|item| render_template(item, alert)).collect()),
+        Value::Object(fields) => {
+            let rendered_fields = fields.iter()
+                .map(|(key, value)| (key.clone(), render_template(value, alert)))
+                .collect();
+            Value::Object(rendered_fields)
+        },
+        value => value.clone()
+    }
+}
+
+/// Hex-encoded HMAC-SHA256 signature of the body
+fn sign_body(secret: &str, body: &[u8]) -> String {
+
+    let key = hmac::Key::new(hmac::HMAC_SHA256, secret.as_bytes());
+    let signature = hmac::sign(&key, body);
+
+    signature.as_ref().iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+
+    use serde_json::json;
+
+    use crate::server::config::AlertSeverity;
+
+    use super::*;
+
+    #[test]
+    fn should_render_body_template() {
+
+        let template = json!({
+            "text": "[{{severity}}] {{message}}",
+            "source": { "region": "{{region}}", "group": "{{group}}" },
+            "duration": "{{duration}}",
+            "tags": ["watchdog", "{{kind}}"]
+        });
+        let webhook = WebhookAlerter::try_new("webhook", "http://localhost:8080", "post", &BTreeMap::new(), Some(template), None).unwrap();
+
+        let alert = Alert::recovery("region-north", Some("default"), AlertSeverity::Warning, "Group \"region-north.default\" is UP again", 125);
+        let body = webhook.render_body(&alert);
+
+        assert_eq!(body["text"], "[warning] Group \"region-north.default\" is UP again");
+        assert_eq!(body["source"], json!({ "region": "region-north", "group": "default" }));
+        assert_eq!(body["duration"], 125);
+        assert_eq!(body["tags"], json!(["watchdog", "recovery"]));
+
+        let alert = Alert::incident("region-north", None, AlertSeverity::Critical, "Region region-north is DOWN");
+        let body = webhook.render_body(&alert);
+
+        assert_eq!(body["source"]["group"], Value::Null);
+        assert_eq!(body["duration"], Value::Null);
+    }
+
+    #[test]
+    fn should_sign_body() {
+
+        // RFC 4231 test case 2
+        let signature = sign_body("Jefe", b"what do ya want for nothing?");
+        assert_eq!(signature, "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843");
+    }
+
+    #[test]
+    fn should_reject_invalid_settings() {
+
+        let invalid_method = WebhookAlerter::try_new("webhook", "http://localhost:8080", "not a method", &BTreeMap::new(), None, None);
+        assert!(invalid_method.is_err());
+
+        let headers = BTreeMap::from([("X-Token\n".to_string(), "abc".to_string())]);
+        let invalid_header = WebhookAlerter::try_new("webhook", "http://localhost:8080", "POST", &headers, None, None);
+        assert!(invalid_header.is_err());
+    }
+
+}
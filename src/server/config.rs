@@ -1,9 +1,14 @@
 use tokio::fs;
-use std::convert::TryFrom;
+use std::{collections::BTreeMap, convert::TryFrom, path::PathBuf};
 
 use serde::{Deserialize, Serialize};
 
 use crate::common::error::Error;
+use crate::server::alert::manager::{medium_settings, requires_recovery};
+use crate::relay::test::runner::TestRunner;
+
+const DEFAULT_CONCURRENCY: usize = 10;
+const DEFAULT_TEST_TIMEOUT_MS: u64 = 10000;
 
 pub struct ServerConf {
 
@@ -13,7 +18,11 @@ pub struct ServerConf {
     pub token: String,
 
     pub telegram_token: Option<String>,
-    pub telegram_chat: Option<String>
+    pub telegram_chat: Option<String>,
+
+    /// Directory of the persistent storage, the server keeps everything
+    /// in memory when no directory is given
+    pub data_dir: Option<PathBuf>
 
 }
 
@@ -27,14 +36,118 @@ pub struct AlerterConfigInput {
     pub medium: String,
     pub chat_env: Option<String>,
     pub token_env: Option<String>,
-    pub recipients_env: Option<String>
+    pub recipients_env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub recovery: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url_env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub method: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub headers: Option<BTreeMap<String, String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub body: Option<serde_json::Value>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub secret_env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub port: Option<u16>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub starttls: Option<bool>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub username_env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub password_env: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub from: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub to: Option<Vec<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub subject: Option<String>
+}
+
+impl AlerterConfigInput {
+
+    /// Medium settings given in the configuration (the name, the medium and the
+    /// recovery flag apply to all mediums)
+    fn configured_fields(&self) -> Vec<&'static str> {
+
+        let fields = [
+            ("chat_env", self.chat_env.is_some()),
+            ("token_env", self.token_env.is_some()),
+            ("recipients_env", self.recipients_env.is_some()),
+            ("url_env", self.url_env.is_some()),
+            ("url", self.url.is_some()),
+            ("method", self.method.is_some()),
+            ("headers", self.headers.is_some()),
+            ("body", self.body.is_some()),
+            ("secret_env", self.secret_env.is_some()),
+            ("host", self.host.is_some()),
+            ("port", self.port.is_some()),
+            ("starttls", self.starttls.is_some()),
+            ("username_env", self.username_env.is_some()),
+            ("password_env", self.password_env.is_some()),
+            ("from", self.from.is_some()),
+            ("to", self.to.is_some()),
+            ("subject", self.subject.is_some())
+        ];
+
+        fields.into_iter()
+            .filter(|(_, configured)| *configured)
+            .map(|(field, _)| field)
+            .collect()
+    }
+
+}
+
+/// Alerters receiving the alerts of a region and/or a group with at least the given
+/// severity, an alert is sent to the alerters of all matching routes
+#[derive(Deserialize, Serialize)]
+pub struct AlertRouteInput {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub group: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<AlertSeverity>,
+    pub alerters: Vec<String>
+}
+
+#[derive(Deserialize, Serialize)]
+pub struct ThresholdConfigInput {
+    pub warn_latency: Option<String>,
+    pub fail_latency: Option<String>,
+    pub warn_loss: Option<f32>,
+    pub fail_loss: Option<f32>
+}
+
+/// A test is either written as a single command ('ping 1.1.1.1 count=5') or as
+/// a map with a type, a target and options (converted into the same command)
+#[derive(Deserialize, Serialize)]
+#[serde(untagged)]
+pub enum TestConfigInput {
+    Command(String),
+    Structured {
+        #[serde(rename = "type")]
+        test_type: String,
+        target: String,
+        #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+        options: BTreeMap<String, serde_yaml::Value>
+    }
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct GroupConfigInput {
     pub name: String,
     pub fail_threshold: Option<u64>,
-    pub tests: Vec<String>
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thresholds: Option<ThresholdConfigInput>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub severity: Option<AlertSeverity>,
+    pub tests: Vec<TestConfigInput>
 }
 
 #[derive(Deserialize, Serialize)]
@@ -43,21 +156,53 @@ pub struct RegionConfigInput {
     pub send_interval: Option<String>,
     pub miss_threshold: Option<u64>,
     pub kuma_url: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub concurrency: Option<usize>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub test_timeout: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub thresholds: Option<ThresholdConfigInput>,
     pub groups: Vec<GroupConfigInput>
 }
 
 #[derive(Deserialize, Serialize)]
 pub struct ConfigInput {
     pub alerters: Option<Vec<AlerterConfigInput>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub alert_routes: Option<Vec<AlertRouteInput>>,
     pub regions: Vec<RegionConfigInput>
 }
 
 // Internal models
 
+/// Severity of an alert, region incidents are critical and group incidents
+/// use the severity of the group (critical by default)
+#[derive(Deserialize,Serialize,Clone,Copy,Default,Debug,PartialEq,PartialOrd)]
+#[serde(rename_all = "lowercase")]
+pub enum AlertSeverity {
+    Warning,
+    #[default]
+    Critical
+}
+
+/// Default warning & failure levels for the tests of a group, a test can still
+/// override these values with its own options (such as 'warn_latency=50ms').
+#[derive(Deserialize,Serialize,Clone,Default,Debug,PartialEq)]
+pub struct ThresholdConfig {
+    pub warn_latency_ms: Option<u64>,
+    pub fail_latency_ms: Option<u64>,
+    pub warn_loss_pct: Option<f32>,
+    pub fail_loss_pct: Option<f32>
+}
+
 #[derive(Deserialize,Serialize,Clone)]
 pub struct GroupConfig {
     pub name: String,
     pub threshold_ms: u64,
+    #[serde(default)]
+    pub thresholds: ThresholdConfig,
+    #[serde(default)]
+    pub severity: AlertSeverity,
     pub tests: Vec<String>
 }
 
@@ -67,6 +212,10 @@ pub struct RegionConfig {
     pub interval_ms: u64,
     pub threshold_ms: u64,
     pub kuma_url: Option<String>,
+    #[serde(default = "default_concurrency")]
+    pub concurrency: usize,
+    #[serde(default = "default_test_timeout_ms")]
+    pub test_timeout_ms: u64,
     pub groups: Vec<GroupConfig>
 }
 
@@ -76,38 +225,86 @@ pub struct AlertConfig {
     pub medium: String,
     pub chat_env: Option<String>,
     pub token_env: Option<String>,
-    pub recipients_env: Option<String>
+    pub recipients_env: Option<String>,
+
+    /// Send a message when a region or a group recovers from an incident
+    pub recovery: bool,
+
+    /// Environment variable of the URL of the chat mediums (Slack, Discord and
+    /// Mattermost incoming webhooks) and of the webhook medium
+    pub url_env: Option<String>,
+
+    /// API URL of the PagerDuty and Opsgenie mediums (such as a regional endpoint)
+    pub url: Option<String>,
+
+    // Webhook settings, the body is a JSON template filled with the alert
+    // fields and the secret is used to sign the body (HMAC-SHA256)
+    pub method: Option<String>,
+    pub headers: BTreeMap<String, String>,
+    pub body: Option<serde_json::Value>,
+    pub secret_env: Option<String>,
+
+    // SMTP settings, the subject is a template filled with the alert fields
+    pub host: Option<String>,
+    pub port: Option<u16>,
+    pub starttls: bool,
+    pub username_env: Option<String>,
+    pub password_env: Option<String>,
+    pub from: Option<String>,
+    pub to: Vec<String>,
+    pub subject: Option<String>
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct AlertRouteConfig {
+    pub region: Option<String>,
+    pub group: Option<String>,
+    pub severity: Option<AlertSeverity>,
+    pub alerters: Vec<String>
+}
+
+impl AlertRouteConfig {
+
+    pub fn matches(&self, region: &str, group: Option<&str>, severity: AlertSeverity) -> bool {
+
+        let region_match = self.region.as_deref().map(|route_region| route_region == region).unwrap_or(true);
+
+        // A route on a group never matches region alerts
+        let group_match = match (self.group.as_deref(), group) {
+            (Some(route_group), Some(group)) => route_group == group,
+            (Some(_), None) => false,
+            (None, _) => true
+        };
+
+        let severity_match = self.severity.map(|min_severity| severity >= min_severity).unwrap_or(true);
+
+        region_match && group_match && severity_match
+    }
+
 }
 
 #[derive(Deserialize,Serialize)]
 pub struct Config {
     pub version: String,
     pub alerters: Vec<AlertConfig>,
+    #[serde(default)]
+    pub alert_routes: Vec<AlertRouteConfig>,
     pub regions: Vec<RegionConfig>
 }
 
 impl Config {
 
-    pub async fn new(config_path: &str) -> Result<Config, Error> {
+    /// Read the YAML configuration file, tests are validated with the test
+    /// types registered in the runner
+    pub async fn new(config_path: &str, runner: &TestRunner) -> Result<Config, Error> {
 
         let contents = fs::read_to_string(config_path).await.map_err(|err| Error::new("Could not read configuration file", err))?;
         let parsed_yaml: ConfigInput = serde_yaml::from_str(&contents).map_err(|err| Error::new("Could not parse YAML", err))?;
 
-        Config::try_from(parsed_yaml).map_err(|err| Error::new("Failed to parse config", err))
-    }
-
-    pub fn export_region(&self, region_name: &str) -> Option<&RegionConfig> {
-
-        self.regions.iter().find(|region| region.name.eq(region_name))
+        Config::try_from_input(parsed_yaml, runner).map_err(|err| Error::new("Failed to parse config", err))
     }
 
-}
-
-impl TryFrom<ConfigInput> for Config{
-
-    type Error = &'static str;
-
-    fn try_from(input: ConfigInput) -> Result<Self, Self::Error> {
+    pub fn try_from_input(input: ConfigInput, runner: &TestRunner) -> Result<Config, String> {
 
         let mut regions: Vec<RegionConfig> = vec![];
         for region_input in input.regions.iter() {
@@ -118,18 +315,48 @@ impl TryFrom<ConfigInput> for Config{
             };
             let region_interval_ms = parse_to_milliseconds(human_readable_interval)?;
 
+            let region_thresholds = ThresholdConfig::default().merge(&region_input.thresholds)?;
+
             let mut groups: Vec<GroupConfig> = vec![];
             for group_input in region_input.groups.iter() {
 
+                // Tests are validated on the server, a typo in a test should not be
+                // discovered as a group failure in a remote region
+                let mut tests: Vec<String> = vec![];
+                for test_input in group_input.tests.iter() {
+
+                    let test = test_input.to_command()?;
+                    runner.parse_test(&test).map_err(|err| {
+                        let reason = err.details.unwrap_or(err.message);
+                        format!("Invalid test '{}' in group '{}' of region '{}': {}", test, group_input.name, region_input.name, reason)
+                    })?;
+
+                    tests.push(test);
+                }
+
                 let group_fail_threshold = group_input.fail_threshold.unwrap_or(3);
                 let group = GroupConfig {
                     name: String::from(&group_input.name),
                     threshold_ms: region_interval_ms * group_fail_threshold + 1000,
-                    tests: group_input.tests.clone()
+                    thresholds: region_thresholds.merge(&group_input.thresholds)?,
+                    severity: group_input.severity.unwrap_or_default(),
+                    tests
                 };
                 groups.push(group);
             }
 
+            // Relays run the tests of a region concurrently, each test is stopped (and
+            // considered as failed) after a timeout, or at the end of the region interval
+            let concurrency = region_input.concurrency.unwrap_or(DEFAULT_CONCURRENCY);
+            if concurrency == 0 {
+                return Err(format!("Region '{}' should have a concurrency of at least 1", region_input.name));
+            }
+
+            let test_timeout_ms = match &region_input.test_timeout {
+                Some(test_timeout) => parse_to_milliseconds(test_timeout)?,
+                None => DEFAULT_TEST_TIMEOUT_MS
+            };
+
             let region_miss_threshold = region_input.miss_threshold.unwrap_or(3);
             let region = RegionConfig {
                 name: String::from(&region_input.name),
@@ -138,36 +365,198 @@ impl TryFrom<ConfigInput> for Config{
                 // after the interval multiple
                 threshold_ms: region_interval_ms * region_miss_threshold + 1000,
                 kuma_url: region_input.kuma_url.clone(),
+                concurrency,
+                test_timeout_ms,
                 groups
             };
             regions.push(region);
         }
 
-        let alerters = match input.alerters {
-            Some(alerters) => {
-                alerters.into_iter().map(|alerter_input| {
-
-                    AlertConfig {
-                        name: alerter_input.name,
-                        medium: alerter_input.medium,
-                        chat_env: alerter_input.chat_env,
-                        token_env: alerter_input.token_env,
-                        recipients_env: alerter_input.recipients_env
-                    }
-        
-                }).collect()
-            },
-            None => vec![]
-        };
+        let mut alerters: Vec<AlertConfig> = vec![];
+        for alerter_input in input.alerters.unwrap_or_default() {
+
+            // Settings of another medium are most likely a mistake (such as a webhook
+            // 'url' on a Slack alerter), they would otherwise be silently ignored
+            if let Some(medium_fields) = medium_settings(&alerter_input.medium) {
+                if let Some(field) = alerter_input.configured_fields().into_iter().find(|field| !medium_fields.contains(field)) {
+                    return Err(format!("Alerter '{}' does not support '{}' with {} medium", alerter_input.name, field, alerter_input.medium));
+                }
+            }
+
+            if alerter_input.recovery == Some(false) && requires_recovery(&alerter_input.medium) {
+                return Err(format!("Alerter '{}' can not disable recovery with {} medium, alerts would never be resolved", alerter_input.name, alerter_input.medium));
+            }
+
+            alerters.push(AlertConfig {
+                name: alerter_input.name,
+                medium: alerter_input.medium,
+                chat_env: alerter_input.chat_env,
+                token_env: alerter_input.token_env,
+                recipients_env: alerter_input.recipients_env,
+                recovery: alerter_input.recovery.unwrap_or(true),
+                url_env: alerter_input.url_env,
+                url: alerter_input.url,
+                method: alerter_input.method,
+                headers: alerter_input.headers.unwrap_or_default(),
+                body: alerter_input.body,
+                secret_env: alerter_input.secret_env,
+                host: alerter_input.host,
+                port: alerter_input.port,
+                starttls: alerter_input.starttls.unwrap_or(true),
+                username_env: alerter_input.username_env,
+                password_env: alerter_input.password_env,
+                from: alerter_input.from,
+                to: alerter_input.to.unwrap_or_default(),
+                subject: alerter_input.subject
+            });
+        }
+
+        // Routes are checked on startup, a typo would otherwise silently drop alerts
+        let mut alert_routes: Vec<AlertRouteConfig> = vec![];
+        for route_input in input.alert_routes.unwrap_or_default() {
+
+            for alerter_name in route_input.alerters.iter() {
+                if !alerters.iter().any(|alerter: &AlertConfig| &alerter.name == alerter_name) {
+                    return Err(format!("Alert route refers to an unknown alerter '{}'", alerter_name));
+                }
+            }
+
+            if let Some(route_region) = &route_input.region {
+                if !regions.iter().any(|region| &region.name == route_region) {
+                    return Err(format!("Alert route refers to an unknown region '{}'", route_region));
+                }
+            }
+
+            if let Some(route_group) = &route_input.group {
+                let group_exists = regions.iter()
+                    .filter(|region| route_input.region.as_ref().map(|route_region| &region.name == route_region).unwrap_or(true))
+                    .any(|region| region.groups.iter().any(|group| &group.name == route_group));
+
+                if !group_exists {
+                    return Err(format!("Alert route refers to an unknown group '{}'", route_group));
+                }
+            }
+
+            alert_routes.push(AlertRouteConfig {
+                region: route_input.region,
+                group: route_input.group,
+                severity: route_input.severity,
+                alerters: route_input.alerters
+            });
+        }
 
         Ok(Config {
             // TODO Better format
             version: chrono::Utc::now().format("%Y-%m-%d %H:%M:%S").to_string(),
             alerters,
+            alert_routes,
             regions
         })
     }
 
+    pub fn export_region(&self, region_name: &str) -> Option<&RegionConfig> {
+
+        self.regions.iter().find(|region| region.name.eq(region_name))
+    }
+
+}
+
+impl TryFrom<ConfigInput> for Config{
+
+    type Error = String;
+
+    fn try_from(input: ConfigInput) -> Result<Self, Self::Error> {
+
+        Config::try_from_input(input, &TestRunner::new())
+    }
+
+}
+
+
+impl TestConfigInput {
+
+    /**
+     * Convert the test into the command executed by relays, such as 'http example.org
+     * method=POST'. Options with a list value are repeated for each item, and values are
+     * quoted when they contain spaces or double quotes.
+     */
+    pub fn to_command(&self) -> Result<String, String> {
+
+        let (test_type, target, options) = match self {
+            TestConfigInput::Command(command) => return Ok(command.trim().to_string()),
+            TestConfigInput::Structured { test_type, target, options } => (test_type, target, options)
+        };
+
+        let mut components = vec![test_type.trim().to_string(), quote_component(target.trim())];
+
+        for (key, value) in options {
+
+            let values = match value {
+                serde_yaml::Value::Sequence(items) => items.iter().collect(),
+                _ => vec![value]
+            };
+
+            for value in values {
+
+                let value = match value {
+                    serde_yaml::Value::String(text) => text.to_string(),
+                    serde_yaml::Value::Number(number) => number.to_string(),
+                    serde_yaml::Value::Bool(boolean) => boolean.to_string(),
+                    _ => return Err(format!("Option '{}' of test '{} {}' must be a string, a number or a list", key, test_type, target))
+                };
+                components.push(format!("{}={}", key, quote_component(&value)));
+            }
+        }
+
+        Ok(components.join(" "))
+    }
+
+}
+
+fn quote_component(component: &str) -> String {
+
+    if component.contains(' ') || component.contains('"') {
+        return format!("\"{}\"", component.replace('"', "\\\""));
+    }
+
+    component.to_string()
+}
+
+impl ThresholdConfig {
+
+    /// Override the current thresholds with the values defined in a YAML
+    /// configuration block (region thresholds are overridden by group ones)
+    pub fn merge(&self, input: &Option<ThresholdConfigInput>) -> Result<ThresholdConfig, &'static str> {
+
+        let input = match input {
+            Some(input) => input,
+            None => return Ok(self.clone())
+        };
+
+        let parse_latency = |latency: &Option<String>| latency.as_deref().map(parse_to_milliseconds).transpose();
+        let parse_loss = |loss: Option<f32>| match loss {
+            Some(loss) if !(0.0..=100.0).contains(&loss) => Err("invalid loss percentage"),
+            _ => Ok(loss)
+        };
+
+        Ok(ThresholdConfig {
+            warn_latency_ms: parse_latency(&input.warn_latency)?.or(self.warn_latency_ms),
+            fail_latency_ms: parse_latency(&input.fail_latency)?.or(self.fail_latency_ms),
+            warn_loss_pct: parse_loss(input.warn_loss)?.or(self.warn_loss_pct),
+            fail_loss_pct: parse_loss(input.fail_loss)?.or(self.fail_loss_pct)
+        })
+    }
+
+}
+
+fn default_concurrency() -> usize {
+
+    DEFAULT_CONCURRENCY
+}
+
+fn default_test_timeout_ms() -> u64 {
+
+    DEFAULT_TEST_TIMEOUT_MS
 }
 
 /**
@@ -264,4 +653,243 @@ mod tests {
         assert_eq!(parse_to_milliseconds("3z"), Err("invalid milliseconds"));
     }
 
+    #[test]
+    fn should_merge_group_thresholds() {
+
+        let region_input = ThresholdConfigInput {
+            warn_latency: Some("600ms".into()),
+            fail_latency: Some("2s".into()),
+            warn_loss: None,
+            fail_loss: None
+        };
+        let group_input = ThresholdConfigInput {
+            warn_latency: Some("800ms".into()),
+            fail_latency: None,
+            warn_loss: Some(20.0),
+            fail_loss: None
+        };
+
+        let region_thresholds = ThresholdConfig::default().merge(&Some(region_input)).unwrap();
+        let group_thresholds = region_thresholds.merge(&Some(group_input)).unwrap();
+
+        assert_eq!(group_thresholds, ThresholdConfig {
+            warn_latency_ms: Some(800),
+            fail_latency_ms: Some(2000),
+            warn_loss_pct: Some(20.0),
+            fail_loss_pct: None
+        });
+        assert_eq!(region_thresholds.merge(&None).unwrap(), region_thresholds);
+    }
+
+    #[test]
+    fn should_deny_invalid_thresholds() {
+
+        let latency_input = ThresholdConfigInput {
+            warn_latency: Some("fast".into()),
+            fail_latency: None,
+            warn_loss: None,
+            fail_loss: None
+        };
+        let loss_input = ThresholdConfigInput {
+            warn_latency: None,
+            fail_latency: None,
+            warn_loss: None,
+            fail_loss: Some(150.0)
+        };
+
+        assert_eq!(ThresholdConfig::default().merge(&Some(latency_input)), Err("invalid milliseconds"));
+        assert_eq!(ThresholdConfig::default().merge(&Some(loss_input)), Err("invalid loss percentage"));
+    }
+
+    fn parse_config(yaml: &str) -> Result<Config, String> {
+
+        let input: ConfigInput = serde_yaml::from_str(yaml).unwrap();
+        Config::try_from(input)
+    }
+
+    #[test]
+    fn should_convert_structured_tests() {
+
+        let config = parse_config(r#"
+regions:
+  - name: region-south
+    groups:
+      - name: default
+        tests:
+          - ping 1.1.1.1
+          - type: ping
+            target: 10.1.2.3
+            options:
+              count: 5
+              warn_latency: 600ms
+          - type: http
+            target: https://example.org/health
+            options:
+              header: ["X-Token: abc", "Accept: application/json"]
+              body_json: $.status == "ok"
+        "#).unwrap();
+
+        assert_eq!(config.regions[0].groups[0].tests, vec![
+            "ping 1.1.1.1".to_string(),
+            "ping 10.1.2.3 count=5 warn_latency=600ms".to_string(),
+            r#"http https://example.org/health body_json="$.status == \"ok\"" header="X-Token: abc" header="Accept: application/json""#.to_string()
+        ]);
+    }
+
+    #[test]
+    fn should_enable_recovery_by_default() {
+
+        let config = parse_config(r#"
+alerters:
+  - name: telegram_default
+    medium: telegram
+  - name: sms
+    medium: spryng
+    recovery: false
+regions: []
+        "#).unwrap();
+
+        assert!(config.alerters[0].recovery);
+        assert!(!config.alerters[1].recovery);
+    }
+
+    #[test]
+    fn should_deny_fields_of_other_mediums() {
+
+        let config = parse_config(r#"
+alerters:
+  - name: team_chat
+    medium: slack
+    url_env: SLACK_WEBHOOK_URL
+    recovery: false
+  - name: pager
+    medium: pagerduty
+    token_env: PAGERDUTY_ROUTING_KEY
+    url: https://events.example.org/v2/enqueue
+regions: []
+        "#);
+        assert!(config.is_ok());
+
+        let config = parse_config(r#"
+alerters:
+  - name: team_chat
+    medium: slack
+    url: https://hooks.slack.com/services/abc
+regions: []
+        "#);
+        assert_eq!(config.err(), Some("Alerter 'team_chat' does not support 'url' with slack medium".to_string()));
+
+        let config = parse_config(r#"
+alerters:
+  - name: oncall_email
+    medium: smtp
+    host: smtp.example.org
+    from: watchdog@example.org
+    secret_env: WEBHOOK_SECRET
+regions: []
+        "#);
+        assert_eq!(config.err(), Some("Alerter 'oncall_email' does not support 'secret_env' with smtp medium".to_string()));
+    }
+
+    #[test]
+    fn should_deny_disabled_recovery_on_paging_mediums() {
+
+        let config = parse_config(r#"
+alerters:
+  - name: pager
+    medium: pagerduty
+    token_env: PAGERDUTY_ROUTING_KEY
+    recovery: false
+regions: []
+        "#);
+
+        assert_eq!(config.err(), Some("Alerter 'pager' can not disable recovery with pagerduty medium, alerts would never be resolved".to_string()));
+    }
+
+    #[test]
+    fn should_deny_unknown_route_alerters() {
+
+        let config = parse_config(r#"
+alerters:
+  - name: telegram_default
+    medium: telegram
+alert_routes:
+  - region: region-south
+    severity: critical
+    alerters: [telegram_default, sms]
+regions:
+  - name: region-south
+    groups:
+      - name: default
+        severity: warning
+        tests:
+          - ping 1.1.1.1
+        "#);
+
+        assert_eq!(config.err(), Some("Alert route refers to an unknown alerter 'sms'".to_string()));
+    }
+
+    #[test]
+    fn should_share_default_test_timeout() {
+
+        let config = parse_config(r#"
+regions:
+  - name: region-south
+    send_interval: 30s
+    groups: []
+        "#).unwrap();
+
+        // Relays may cache a configuration exported without the test timeout
+        let mut exported_region = serde_json::to_value(config.export_region("region-south").unwrap()).unwrap();
+        exported_region.as_object_mut().unwrap().remove("test_timeout_ms");
+        let cached_region: RegionConfig = serde_json::from_value(exported_region).unwrap();
+
+        assert_eq!(config.regions[0].test_timeout_ms, DEFAULT_TEST_TIMEOUT_MS);
+        assert_eq!(cached_region.test_timeout_ms, DEFAULT_TEST_TIMEOUT_MS);
+    }
+
+    #[test]
+    fn should_deny_invalid_tests() {
+
+        let unknown_type = parse_config(r#"
+regions:
+  - name: region-south
+    groups:
+      - name: default
+        tests:
+          - pign 1.1.1.1
+        "#);
+        assert_eq!(
+            unknown_type.err(),
+            Some("Invalid test 'pign 1.1.1.1' in group 'default' of region 'region-south': Test 'pign 1.1.1.1' failed, command not found".to_string())
+        );
+
+        let invalid_option = parse_config(r#"
+regions:
+  - name: region-south
+    groups:
+      - name: default
+        tests:
+          - type: ping
+            target: 10.1.2.3
+            options:
+              count: 1000
+        "#);
+        assert!(invalid_option.err().unwrap().contains("Option 'count' expects a number between 1 and 100"));
+
+        let invalid_value = parse_config(r#"
+regions:
+  - name: region-south
+    groups:
+      - name: default
+        tests:
+          - type: http
+            target: example.org
+            options:
+              header:
+                name: X-Token
+        "#);
+        assert!(invalid_value.is_err());
+    }
+
 }
@@ -1,19 +1,34 @@
 use std::sync::Arc;
 
 use axum::{
-    extract::{Path, State},
+    extract::{Path, Query, State},
     http::{HeaderMap, header, StatusCode},
     Json,
     response::IntoResponse,
 };
+use chrono::{DateTime, Utc};
+use serde::Deserialize;
 use serde_json::json;
 
-use crate::relay::model::GroupResultInput;
-use crate::server::storage::{GroupState, RegionState};
+use crate::common::utils::format_duration;
+use crate::relay::model::{GroupResultInput, ProbeResultInput, RegionBackfillInput};
+use crate::server::storage::GroupState;
 
-use super::{config::RegionConfig, service::AppState};
+use super::{config::{AlertSeverity, RegionConfig}, service::AppState};
+use super::alert::manager::Alert;
 use super::utils::ServerErr;
-use super::storage::{RegionSummary, IncidentItem, GroupMetrics};
+use super::storage::{RegionSummary, IncidentItem, GroupHistoryItem, GroupHistoryRecord, GroupMetrics, GroupReport, TestOutcome, ProbeItem, ProbeResult};
+
+#[derive(Deserialize)]
+pub struct HistoryQuery {
+    region: Option<String>
+}
+
+#[derive(Deserialize)]
+pub struct ProbeInput {
+    region: String,
+    test: String
+}
 
 pub async fn handle_not_found() -> impl IntoResponse {
     ServerErr::not_found("Endpoint not found")
@@ -74,8 +89,10 @@ pub async fn handle_region_update(Path(region_name): Path<String>, State(state):
     let storage = state.storage.clone();
     let config = state.config.clone();
 
+    let mut recovery_alerts: Vec<Alert> = vec![];
+
     // TODO Blocking RW too long
-    {
+    let probes = {
         let mut write_lock = storage.write().await;
 
         let mut has_warning = false;
@@ -93,6 +110,31 @@ pub async fn handle_region_update(Path(region_name): Path<String>, State(state):
                 (false, _) => GroupState::Down
             };
 
+            let report = build_group_report(&group);
+
+            write_lock.record_history(GroupHistoryRecord {
+                region: region_name.clone(),
+                group: group.name.clone(),
+                timestamp: Utc::now(),
+                working: group.working,
+                has_warnings: group.has_warnings,
+                backfilled: false,
+                report: report.clone()
+            });
+
+            // A working group (even with warnings) resolves the open incidents of the group
+            if group.working {
+                for incident in write_lock.resolve_group_incidents(&region_name, &group.name) {
+                    println!("Incident {} resolved on group {}.{} after {}s", incident.id, region_name, group.name, incident.duration_secs);
+                    let severity = config.export_region(&region_name)
+                        .and_then(|region| region.groups.iter().find(|group_config| group_config.name == group.name))
+                        .map(|group_config| group_config.severity)
+                        .unwrap_or_default();
+                    let message = format!("Group {}.{} is UP again (outage of {})", region_name, group.name, format_duration(incident.duration_secs));
+                    recovery_alerts.push(Alert::recovery(&region_name, Some(&group.name), severity, message, incident.duration_secs));
+                }
+            }
+
             let current_state = write_lock.get_group_status(&region_name, &group.name).map(|state| state.status.clone());
         
             // If there is an ongoing incident on the group and the group is -still- not working,
@@ -102,33 +144,38 @@ pub async fn handle_region_update(Path(region_name): Path<String>, State(state):
                 continue;
             }
 
-            let mut metrics: Vec<GroupMetrics> = vec![];
-            for group_metric in group.metrics {
-
-                metrics.push(GroupMetrics {
-                    name: group_metric.name,
-                    labels: group_metric.labels,
-                    metric: group_metric.metric
-                });
-            }
-
-            write_lock.refresh_group(&region_name, &group.name, group_state, metrics, group.error_message).unwrap_or_else(|err| {
+            write_lock.refresh_group(&region_name, &group.name, group_state, report).unwrap_or_else(|err| {
                 eprintln!("Could not refresh group, can cause unstable storage: {}", err);
             });
         }
 
-        let region_status = write_lock.get_region_status(&region_name);
-
-        if let Some(status) = region_status {
-
-            // We already had an incident and the region was therefore marked as DOWN.
-            // Since we now have a 'heartbeat' on the region level, the incident can be resolved
-            if let RegionState::Down = status.status {
-                println!("INCIDENT RESOLVED ON REGION {}", region_name);
-            }
+        // We may already have an incident and the region was therefore marked as DOWN.
+        // Since we now have a 'heartbeat' on the region level, the incident can be resolved
+        for incident in write_lock.resolve_region_incidents(&region_name) {
+            println!("Incident {} resolved on region {} after {}s", incident.id, region_name, incident.duration_secs);
+            let message = format!("Region {} is UP again (outage of {})", region_name, format_duration(incident.duration_secs));
+            recovery_alerts.push(Alert::recovery(&region_name, None, AlertSeverity::Critical, message, incident.duration_secs));
         }
 
         write_lock.refresh_region(&region_name, has_warning);
+
+        // On-demand probes queued from the CLI are handed to the relay in the
+        // update response, the relay sends the results on a dedicated route
+        write_lock.take_pending_probes(&region_name)
+    };
+
+    // Recovery messages are sent in the background, the relay must not wait
+    // for the alerting mediums
+    if !recovery_alerts.is_empty() {
+
+        let alert_manager = state.alert.clone();
+        tokio::spawn(async move {
+            for alert in recovery_alerts {
+                alert_manager.alert(&alert).await.unwrap_or_else(|err| {
+                    eprintln!("Error while triggering recovery alert: {}", err);
+                });
+            }
+        });
     }
 
     let mut headers = HeaderMap::new();
@@ -140,12 +187,143 @@ pub async fn handle_region_update(Path(region_name): Path<String>, State(state):
         StatusCode::OK,
         headers,
         Json(json!({
-            "result": true
+            "result": true,
+            "probes": probes
         })),
     )
 
 }
 
+/// Region updates that the relay could not send on time (server unreachable) are
+/// only kept in the history: they are not heartbeats and do not change the status
+/// of the region or its groups
+pub async fn handle_region_backfill(Path(region_name): Path<String>, State(state): State<Arc<AppState>>, Json(updates): Json<Vec<RegionBackfillInput>>) -> Result<Json<serde_json::Value>, ServerErr> {
+
+    if state.config.export_region(&region_name).is_none() {
+        let error_message = format!("Relay configuration not found for region {}", region_name);
+        return Err(ServerErr::not_found(error_message));
+    }
+
+    let mut records: Vec<GroupHistoryRecord> = vec![];
+    for update in updates {
+
+        let timestamp = DateTime::parse_from_rfc3339(&update.timestamp)
+            .map_err(|_| ServerErr::bad_request(format!("Invalid update timestamp '{}'", update.timestamp)))?
+            .with_timezone(&Utc);
+
+        for group in update.groups {
+
+            records.push(GroupHistoryRecord {
+                region: region_name.clone(),
+                group: group.name.clone(),
+                timestamp,
+                working: group.working,
+                has_warnings: group.has_warnings,
+                backfilled: true,
+                report: build_group_report(&group)
+            });
+        }
+    }
+
+    let backfilled_count = records.len();
+    {
+        let mut write_lock = state.storage.write().await;
+        for record in records {
+            write_lock.record_history(record);
+        }
+    }
+
+    Ok(Json(json!({
+        "result": true,
+        "backfilled": backfilled_count
+    })))
+}
+
+pub async fn handle_find_history(Query(query): Query<HistoryQuery>, State(state): State<Arc<AppState>>) -> Result<Json<Vec<GroupHistoryItem>>, ServerErr> {
+
+    let storage = state.storage.clone();
+
+    let history = storage.read().await.find_history(query.region.as_deref());
+
+    Ok(history.into())
+}
+
+fn build_group_report(group: &GroupResultInput) -> GroupReport {
+
+    let metrics: Vec<GroupMetrics> = group.metrics.iter().map(|group_metric| GroupMetrics {
+        name: group_metric.name.clone(),
+        labels: group_metric.labels.clone(),
+        metric: group_metric.metric
+    }).collect();
+
+    let tests: Vec<TestOutcome> = group.tests.iter().map(|outcome| TestOutcome {
+        test: outcome.test.clone(),
+        target: outcome.target.clone(),
+        result: outcome.result,
+        duration_ms: outcome.duration_ms,
+        reason: outcome.reason.clone(),
+        error: outcome.error.clone()
+    }).collect();
+
+    GroupReport {
+        metrics,
+        tests,
+        error: group.error_message.clone(),
+        error_detail: group.error_detail.clone()
+    }
+}
+
+pub async fn handle_queue_probe(State(state): State<Arc<AppState>>, Json(input): Json<ProbeInput>) -> Result<Json<serde_json::Value>, ServerErr> {
+
+    if state.config.export_region(&input.region).is_none() {
+        let error_message = format!("Relay configuration not found for region {}", input.region);
+        return Err(ServerErr::not_found(error_message));
+    }
+
+    state.runner.parse_test(&input.test).map_err(|err| {
+        ServerErr::bad_request(format!("Invalid test '{}': {}", input.test, err))
+    })?;
+
+    let probe_id = state.storage.write().await.queue_probe(&input.region, &input.test);
+
+    Ok(Json(json!({
+        "result": true,
+        "id": probe_id
+    })))
+}
+
+pub async fn handle_get_probe(Path(probe_id): Path<u32>, State(state): State<Arc<AppState>>) -> Result<Json<ProbeItem>, ServerErr> {
+
+    let storage = state.storage.clone();
+
+    let probe_result = storage.read().await.get_probe(probe_id);
+
+    if let Some(result) = probe_result {
+        return Ok(result.into())
+    }
+
+    Err(ServerErr::not_found("Could not find probe"))
+}
+
+pub async fn handle_probe_result(Path((region_name, probe_id)): Path<(String, u32)>, State(state): State<Arc<AppState>>, Json(input): Json<ProbeResultInput>) -> Result<Json<serde_json::Value>, ServerErr> {
+
+    let probe_result = ProbeResult {
+        target: input.target,
+        result: input.result,
+        duration_ms: input.duration_ms,
+        reason: input.reason,
+        error: input.error,
+        metrics: input.metrics
+    };
+
+    state.storage.write().await.complete_probe(&region_name, probe_id, probe_result)
+        .map_err(|err| ServerErr::not_found(err.message))?;
+
+    Ok(Json(json!({
+        "result": true
+    })))
+}
+
 pub async fn handle_find_incidents(State(state): State<Arc<AppState>>) -> Result<Json<Vec<IncidentItem>>, ServerErr> {
 
     let storage = state.storage.clone();
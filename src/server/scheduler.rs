@@ -9,8 +9,8 @@ use crate::server::storage::{RegionStatus, GroupStatus, GroupState, RegionState}
 use crate::server::storage::Storage;
 use crate::server::config::Config;
 
-use super::alert::manager::AlertManager;
-use super::config::{RegionConfig, GroupConfig};
+use super::alert::manager::{Alert, AlertManager};
+use super::config::{AlertSeverity, RegionConfig, GroupConfig};
 
 // TODO Should review defaults
 const DEFAULT_REGION_MS: i64 = 10 * 1000;
@@ -81,7 +81,8 @@ async fn detect_region_incident(region: &RegionConfig, region_status: Option<Reg
                     }
 
                     let message = format!("Region {} is DOWN (no heartbeat received from relay in {}ms)", &region.name, region_ms);
-                    manager.alert(None, &message).await.unwrap_or_else(|err| {
+                    let alert = Alert::incident(&region.name, None, AlertSeverity::Critical, message);
+                    manager.alert(&alert).await.unwrap_or_else(|err| {
                         eprintln!("Error while triggering alert: {}", err);
                     });
                 }
@@ -113,7 +114,8 @@ async fn detect_group_incident(region: &RegionConfig, group: &GroupConfig, group
                     }
 
                     let message = format!("Group {}.{} is DOWN ({})", &region.name, &group.name, status.last_error.unwrap_or("-".into()));
-                    manager.alert(None, &message).await.unwrap_or_else(|err| {
+                    let alert = Alert::incident(&region.name, Some(&group.name), group.severity, message);
+                    manager.alert(&alert).await.unwrap_or_else(|err| {
                         eprintln!("Error while triggering alert: {}", err);
                     });
                 }
@@ -14,7 +14,8 @@ use tower::{BoxError, ServiceBuilder};
 
 use crate::{common::error::Error, server::{middleware::{check_authorization, log_request}, alert::manager::AlertManager}};
 use crate::server::config::Config;
-use crate::server::storage::{MemoryStorage, Storage};
+use crate::relay::test::runner::TestRunner;
+use crate::server::storage::{Storage, file::FileStorage, memory::MemoryStorage};
 use crate::server::scheduler::launch_scheduler;
 
 use super::config::ServerConf;
@@ -26,24 +27,37 @@ pub const DEFAULT_ADDRESS: &str = "127.0.0.1";
 pub struct AppState {
     pub storage: Storage,
     pub config: Arc<Config>,
-    pub alert: Arc<AlertManager>
+    pub alert: Arc<AlertManager>,
+    pub runner: Arc<TestRunner>
 }
 
 pub async fn launch(server_conf: ServerConf) -> Result<(), Error> {
 
-    let storage = MemoryStorage::new();
+    launch_with_runner(server_conf, TestRunner::new()).await
+}
+
+/// Launch the server with a custom test runner, used to validate the tests
+/// of the configuration (see the relay equivalent for custom test types)
+pub async fn launch_with_runner(server_conf: ServerConf, runner: TestRunner) -> Result<(), Error> {
+
+    // Incidents and group history only survive restarts with a data directory
+    let storage: Storage = match &server_conf.data_dir {
+        Some(data_dir) => Arc::new(RwLock::new(FileStorage::open(&data_dir.join("storage.jsonl"))?)),
+        None => Arc::new(RwLock::new(MemoryStorage::new()))
+    };
 
     let config = Arc::new(
-        Config::new(&server_conf.config_path).await?
+        Config::new(&server_conf.config_path, &runner).await?
     );
 
-    let alert_manager = AlertManager::try_from_config(&config.alerters)?;
+    let alert_manager = AlertManager::try_from_config(&config.alerters, &config.alert_routes)?;
     let shared_alert = Arc::new(alert_manager);
 
     let app_state = Arc::new(AppState {
         storage: storage.clone(),
         config: config.clone(),
-        alert: shared_alert.clone()
+        alert: shared_alert.clone(),
+        runner: Arc::new(runner)
     });
 
     let shared_server_conf = Arc::new(server_conf);
@@ -84,6 +98,26 @@ pub async fn launch(server_conf: ServerConf) -> Result<(), Error> {
             get(handle_get_config)
             .put(handle_region_update)
         )
+        .route(
+            "/api/v1/relay/:region_name/backfill",
+            post(handle_region_backfill)
+        )
+        .route(
+            "/api/v1/relay/:region_name/probes/:probe_id",
+            post(handle_probe_result)
+        )
+        .route(
+            "/api/v1/probes",
+            post(handle_queue_probe)
+        )
+        .route(
+            "/api/v1/probes/:probe_id",
+            get(handle_get_probe)
+        )
+        .route(
+            "/api/v1/history",
+            get(handle_find_history)
+        )
         .route(
             "/api/v1/analytics",
             get(handle_analytics)
@@ -153,7 +187,7 @@ pub async fn launch(server_conf: ServerConf) -> Result<(), Error> {
 }
 
 
-async fn init_storage_regions(storage: Arc<RwLock<MemoryStorage>>, config: Arc<Config>) {
+async fn init_storage_regions(storage: Storage, config: Arc<Config>) {
 
     let mut write_lock = storage.write().await;
             
@@ -0,0 +1,420 @@
+use std::fs;
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::thread::{self, JoinHandle};
+
+use tokio::sync::mpsc::{self, UnboundedReceiver, UnboundedSender};
+
+use crate::common::error::Error;
+use crate::relay::model::ProbeRequest;
+
+use super::memory::MemoryStorage;
+use super::*;
+
+/// Number of journal entries that no longer match the stored data (such as history
+/// records dropped from the history) before the journal is rewritten
+const COMPACTION_THRESHOLD: usize = 10000;
+
+#[derive(Deserialize,Serialize)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+enum JournalEntry {
+    Incident(IncidentRecord),
+    IncidentResolved { id: u32, resolved_at: DateTime<Utc> },
+    History(GroupHistoryRecord)
+}
+
+/// Operations performed by the journal writer thread
+enum JournalCommand {
+    Append(JournalEntry),
+    Rewrite(Vec<JournalEntry>)
+}
+
+/**
+ * Persistent storage backend, the incidents and the group history are appended to a
+ * journal file (one JSON entry per line) and replayed on startup. All reads are served
+ * from memory, the live status of the regions and groups and the probes are not
+ * persisted (the relays refresh them within a few seconds).
+ *
+ * The journal is written by a dedicated thread: the storage only queues the entries,
+ * the API handlers never wait on the disk while holding the storage lock.
+ */
+pub struct FileStorage {
+    memory: MemoryStorage,
+    entry_count: usize,
+    journal: Option<UnboundedSender<JournalCommand>>,
+    writer: Option<JoinHandle<()>>
+}
+
+impl FileStorage {
+
+    pub fn open(path: &Path) -> Result<FileStorage, Error> {
+
+        if let Some(parent_dir) = path.parent() {
+            fs::create_dir_all(parent_dir)
+                .map_err(|err| Error::new(format!("Could not create storage directory {}", parent_dir.display()), err))?;
+        }
+
+        let content = match fs::read_to_string(path) {
+            Ok(content) => content,
+            Err(err) if err.kind() == std::io::ErrorKind::NotFound => String::new(),
+            Err(err) => return Err(Error::new(format!("Could not read storage journal {}", path.display()), err))
+        };
+
+        let mut memory = MemoryStorage::new();
+        let mut entry_count = 0;
+
+        // Lines that can not be decoded (such as a partial write) are skipped
+        for entry in content.lines().filter_map(|line| serde_json::from_str::<JournalEntry>(line).ok()) {
+
+            match entry {
+                JournalEntry::Incident(incident) => memory.restore_incident(incident),
+                JournalEntry::IncidentResolved { id, resolved_at } => memory.restore_resolution(id, resolved_at),
+                JournalEntry::History(record) => memory.record_history(record)
+            }
+            entry_count += 1;
+        }
+
+        let mut storage = FileStorage {
+            memory,
+            entry_count,
+            journal: None,
+            writer: None
+        };
+
+        // The server is not started yet, the journal can be compacted before
+        // handing it over to the writer thread
+        if storage.entry_count > storage.live_count() {
+            rewrite_journal(path, &storage.snapshot_entries())?;
+            storage.entry_count = storage.live_count();
+        }
+
+        let journal_file = open_journal(path)?;
+        let (journal_sender, journal_receiver) = mpsc::unbounded_channel();
+
+        let journal_path = path.to_path_buf();
+        let writer = thread::Builder::new()
+            .name("storage-journal".to_string())
+            .spawn(move || run_journal_writer(journal_path, journal_file, journal_receiver))
+            .map_err(|err| Error::new("Could not start storage journal writer", err))?;
+
+        storage.journal = Some(journal_sender);
+        storage.writer = Some(writer);
+
+        Ok(storage)
+    }
+
+    fn live_count(&self) -> usize {
+        self.memory.incident_records().len() + self.memory.history_records().len()
+    }
+
+    fn snapshot_entries(&self) -> Vec<JournalEntry> {
+
+        let incident_entries = self.memory.incident_records().iter().cloned().map(JournalEntry::Incident);
+        let history_entries = self.memory.history_records().iter().cloned().map(JournalEntry::History);
+
+        incident_entries.chain(history_entries).collect()
+    }
+
+    fn send(&self, command: JournalCommand) {
+
+        let send_result = self.journal.as_ref().map(|journal| journal.send(command).is_ok());
+        if send_result != Some(true) {
+            eprintln!("Could not persist storage entry (journal writer stopped), data will be lost on restart");
+        }
+    }
+
+    fn append(&mut self, entry: JournalEntry) {
+
+        self.send(JournalCommand::Append(entry));
+
+        // History records dropped from memory are still in the journal, the journal
+        // is rewritten with the stored data only once too many entries are stale
+        self.entry_count += 1;
+        if self.entry_count >= self.live_count() + COMPACTION_THRESHOLD {
+            self.send(JournalCommand::Rewrite(self.snapshot_entries()));
+            self.entry_count = self.live_count();
+        }
+    }
+
+    fn append_resolutions(&mut self, resolved_incidents: &[IncidentItem]) {
+
+        for incident in resolved_incidents {
+
+            let resolved_at = self.memory.incident_records().iter()
+                .find(|record| record.id == incident.id)
+                .and_then(|record| record.resolved_at);
+
+            if let Some(resolved_at) = resolved_at {
+                self.append(JournalEntry::IncidentResolved { id: incident.id, resolved_at });
+            }
+        }
+    }
+
+    fn append_incidents_from(&mut self, first_index: usize) {
+
+        let new_incidents: Vec<IncidentRecord> = self.memory.incident_records()[first_index..].to_vec();
+        for incident in new_incidents {
+            self.append(JournalEntry::Incident(incident));
+        }
+    }
+
+}
+
+impl Drop for FileStorage {
+
+    /// Wait for the queued entries to be written (the writer stops once the
+    /// channel is closed and drained)
+    fn drop(&mut self) {
+
+        self.journal.take();
+        if let Some(writer) = self.writer.take() {
+            let _ = writer.join();
+        }
+    }
+
+}
+
+fn open_journal(path: &Path) -> Result<fs::File, Error> {
+
+    fs::OpenOptions::new().create(true).append(true).open(path)
+        .map_err(|err| Error::new(format!("Could not open storage journal {}", path.display()), err))
+}
+
+fn encode_entry(entry: &JournalEntry) -> Result<String, Error> {
+
+    let mut line = serde_json::to_string(entry)
+        .map_err(|err| Error::new("Could not encode storage entry", err))?;
+    line.push('\n');
+
+    Ok(line)
+}
+
+/// Rewrite the journal with the given entries only (such as the stored data, without
+/// the history records dropped from memory)
+fn rewrite_journal(path: &Path, entries: &[JournalEntry]) -> Result<(), Error> {
+
+    let mut content = String::new();
+    for entry in entries {
+        content.push_str(&encode_entry(entry)?);
+    }
+
+    // Written in a temporary file first, a crash while writing must not
+    // lose the incident history
+    let temporary_path = path.with_extension("tmp");
+    fs::write(&temporary_path, content)
+        .map_err(|err| Error::new(format!("Could not write storage journal {}", temporary_path.display()), err))?;
+    fs::rename(&temporary_path, path)
+        .map_err(|err| Error::new(format!("Could not replace storage journal {}", path.display()), err))?;
+
+    Ok(())
+}
+
+/// Write the queued journal commands until the storage is dropped, the journal file
+/// stays open between entries (and is reopened after a rewrite)
+fn run_journal_writer(path: PathBuf, mut journal_file: fs::File, mut commands: UnboundedReceiver<JournalCommand>) {
+
+    while let Some(command) = commands.blocking_recv() {
+
+        let write_result = match command {
+            JournalCommand::Append(entry) => encode_entry(&entry).and_then(|line| {
+                journal_file.write_all(line.as_bytes())
+                    .map_err(|err| Error::new(format!("Could not write storage journal {}", path.display()), err))
+            }),
+            JournalCommand::Rewrite(entries) => rewrite_journal(&path, &entries)
+                .and_then(|_| open_journal(&path))
+                .map(|reopened_file| journal_file = reopened_file)
+        };
+
+        if let Err(err) = write_result {
+            eprintln!("Could not persist storage entry, data will be lost on restart: {}", err);
+            if let Some(details) = err.details {
+                eprintln!("{}", details);
+            }
+        }
+    }
+}
+
+impl StorageBackend for FileStorage {
+
+    fn init_region(&mut self, region: &str, linked_groups: Vec<String>) {
+        self.memory.init_region(region, linked_groups)
+    }
+
+    fn init_group(&mut self, region: &str, group: &str) {
+
+        // A group still down after a restart must not open a second incident (and
+        // alert again), only the incident status stops new incidents
+        self.memory.init_group(region, group);
+        self.memory.restore_group_incident(region, group);
+    }
+
+    fn get_region_status(&self, region: &str) -> Option<&RegionStatus> {
+        self.memory.get_region_status(region)
+    }
+
+    fn get_group_status(&self, region: &str, group: &str) -> Option<&GroupStatus> {
+        self.memory.get_group_status(region, group)
+    }
+
+    fn collect_test_metrics(&self) -> Vec<FullMetric> {
+        self.memory.collect_test_metrics()
+    }
+
+    fn collect_region_metrics(&self) -> Vec<FullMetric> {
+        self.memory.collect_region_metrics()
+    }
+
+    fn record_history(&mut self, record: GroupHistoryRecord) {
+
+        self.append(JournalEntry::History(record.clone()));
+        self.memory.record_history(record);
+    }
+
+    fn find_history(&self, region: Option<&str>) -> Vec<GroupHistoryItem> {
+        self.memory.find_history(region)
+    }
+
+    fn queue_probe(&mut self, region: &str, test: &str) -> u32 {
+        self.memory.queue_probe(region, test)
+    }
+
+    fn take_pending_probes(&mut self, region: &str) -> Vec<ProbeRequest> {
+        self.memory.take_pending_probes(region)
+    }
+
+    fn complete_probe(&mut self, region: &str, probe_id: u32, result: ProbeResult) -> Result<(), Error> {
+        self.memory.complete_probe(region, probe_id, result)
+    }
+
+    fn get_probe(&self, probe_id: u32) -> Option<ProbeItem> {
+        self.memory.get_probe(probe_id)
+    }
+
+    fn find_incidents(&self) -> Vec<IncidentItem> {
+        self.memory.find_incidents()
+    }
+
+    fn get_incident(&self, incident_id: u32) -> Option<IncidentItem> {
+        self.memory.get_incident(incident_id)
+    }
+
+    fn compute_analytics(&self) -> RegionSummary {
+        self.memory.compute_analytics()
+    }
+
+    fn refresh_region(&mut self, region: &str, has_warnings: bool) {
+        self.memory.refresh_region(region, has_warnings)
+    }
+
+    fn trigger_region_incident(&mut self, region: &str, ms_threshold: i64) -> Result<(), Error> {
+
+        let incident_count = self.memory.incident_records().len();
+        self.memory.trigger_region_incident(region, ms_threshold)?;
+        self.append_incidents_from(incident_count);
+
+        Ok(())
+    }
+
+    fn refresh_group(&mut self, region: &str, group: &str, status: GroupState, report: GroupReport) -> Result<(), Error> {
+        self.memory.refresh_group(region, group, status, report)
+    }
+
+    fn trigger_group_incident(&mut self, region: &str, group: &str) -> Result<(), Error> {
+
+        let incident_count = self.memory.incident_records().len();
+        self.memory.trigger_group_incident(region, group)?;
+        self.append_incidents_from(incident_count);
+
+        Ok(())
+    }
+
+    fn resolve_region_incidents(&mut self, region: &str) -> Vec<IncidentItem> {
+
+        let resolved_incidents = self.memory.resolve_region_incidents(region);
+        self.append_resolutions(&resolved_incidents);
+        resolved_incidents
+    }
+
+    fn resolve_group_incidents(&mut self, region: &str, group: &str) -> Vec<IncidentItem> {
+
+        let resolved_incidents = self.memory.resolve_group_incidents(region, group);
+        self.append_resolutions(&resolved_incidents);
+        resolved_incidents
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn build_storage_path(name: &str) -> PathBuf {
+
+        let path = std::env::temp_dir().join(format!("watchdog-{}-{}", std::process::id(), name)).join("storage.jsonl");
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    fn open_storage(path: &Path) -> FileStorage {
+
+        let mut storage = FileStorage::open(path).unwrap();
+        storage.init_region("region-north", vec!["default".to_string()]);
+        storage.init_group("region-north", "default");
+        storage
+    }
+
+    #[test]
+    fn should_keep_incidents_between_restarts() {
+
+        let path = build_storage_path("restart");
+
+        let mut storage = open_storage(&path);
+        storage.trigger_region_incident("region-north", 10000).unwrap();
+        storage.record_history(GroupHistoryRecord {
+            region: "region-north".to_string(),
+            group: "default".to_string(),
+            timestamp: Utc::now(),
+            working: false,
+            has_warnings: false,
+            backfilled: false,
+            report: GroupReport {
+                metrics: vec![],
+                tests: vec![],
+                error: Some("test 'ping 10.0.0.1' failed (100% packet loss)".to_string()),
+                error_detail: None
+            }
+        });
+
+        // Dropping the storage waits for the queued entries to be written
+        drop(storage);
+        let mut storage = open_storage(&path);
+        storage.resolve_region_incidents("region-north");
+        storage.trigger_group_incident("region-north", "default").unwrap();
+
+        let incidents = storage.find_incidents();
+        let incident_ids: Vec<u32> = incidents.iter().map(|incident| incident.id).collect();
+        assert_eq!(incident_ids, vec![0, 1]);
+        assert_eq!(incidents[0].message, "Region region-north is DOWN");
+        assert_eq!(incidents[0].status, IncidentStatus::Resolved);
+        assert_eq!(incidents[1].status, IncidentStatus::Open);
+
+        let history = storage.find_history(Some("region-north"));
+        assert_eq!(history.len(), 1);
+        assert_eq!(history[0].error_message.as_deref(), Some("test 'ping 10.0.0.1' failed (100% packet loss)"));
+
+        // The group status is not persisted (the relays refresh it after a restart),
+        // except for the groups with an open incident
+        drop(storage);
+        let mut storage = open_storage(&path);
+        assert!(matches!(storage.get_group_status("region-north", "default").unwrap().status, GroupState::Incident));
+
+        storage.resolve_group_incidents("region-north", "default");
+        drop(storage);
+        let storage = open_storage(&path);
+        assert!(matches!(storage.get_group_status("region-north", "default").unwrap().status, GroupState::Initial));
+        assert_eq!(storage.find_incidents().len(), 2);
+        assert!(storage.get_incident(0).unwrap().resolved_at.is_some());
+    }
+
+}
@@ -0,0 +1,598 @@
+use std::collections::{HashMap, VecDeque};
+
+use chrono::{DateTime, Duration, Utc};
+
+use crate::common::error::Error;
+use crate::relay::model::ProbeRequest;
+
+use super::*;
+
+const MAX_HISTORY_RECORDS: usize = 10000;
+const MAX_PROBE_RECORDS: usize = 100;
+const PROBE_EXPIRY_SECONDS: i64 = 300;
+
+struct RegionMetadata {
+    linked_groups: Vec<String>
+}
+
+/// Default storage backend, all data is lost when the server stops
+pub struct MemoryStorage {
+    region_storage: HashMap<String, RegionStatus>,
+    region_metadata: HashMap<String, RegionMetadata>,
+    group_storage: HashMap<String, GroupStatus>,
+    group_history: VecDeque<GroupHistoryRecord>,
+    incidents: Vec<IncidentRecord>,
+    last_incident_id: u32,
+    probes: VecDeque<ProbeRecord>,
+    last_probe_id: u32
+}
+
+impl MemoryStorage {
+
+    pub fn new() -> Self {
+
+        MemoryStorage {
+            region_storage: HashMap::new(),
+            region_metadata: HashMap::new(),
+            group_storage: HashMap::new(),
+            group_history: VecDeque::new(),
+            incidents: Vec::new(),
+            last_incident_id: 0,
+            probes: VecDeque::new(),
+            last_probe_id: 0
+        }
+    }
+
+    pub(super) fn incident_records(&self) -> &[IncidentRecord] {
+        &self.incidents
+    }
+
+    pub(super) fn history_records(&self) -> &VecDeque<GroupHistoryRecord> {
+        &self.group_history
+    }
+
+    fn resolve_incidents(&mut self, region: &str, group: Option<&str>) -> Vec<IncidentItem> {
+
+        let resolved_at = Utc::now();
+
+        let mut resolved_incidents: Vec<IncidentItem> = vec![];
+        for incident in self.incidents.iter_mut() {
+
+            if incident.resolved_at.is_some() || incident.region != region || incident.group.as_deref() != group {
+                continue;
+            }
+
+            incident.resolved_at = Some(resolved_at);
+            resolved_incidents.push(incident.to_item());
+        }
+
+        resolved_incidents
+    }
+
+    /// Restore a resolution persisted by a previous server run
+    pub(super) fn restore_resolution(&mut self, incident_id: u32, resolved_at: DateTime<Utc>) {
+
+        if let Some(incident) = self.incidents.iter_mut().find(|incident| incident.id == incident_id) {
+            incident.resolved_at = Some(resolved_at);
+        }
+    }
+
+    /// Restore an incident persisted by a previous server run, incident IDs
+    /// continue after the highest restored ID
+    pub(super) fn restore_incident(&mut self, incident: IncidentRecord) {
+
+        self.last_incident_id = std::cmp::max(self.last_incident_id, incident.id + 1);
+        self.incidents.push(incident);
+    }
+
+    /// Restore the incident status of a group with an incident still open when
+    /// the previous server run stopped
+    pub(super) fn restore_group_incident(&mut self, region: &str, group: &str) {
+
+        let has_open_incident = self.incidents.iter().any(|incident| {
+            incident.resolved_at.is_none() && incident.region == region && incident.group.as_deref() == Some(group)
+        });
+
+        if !has_open_incident {
+            return;
+        }
+
+        if let Some(group_status) = self.group_storage.get_mut(&format!("{}.{}", region, group)) {
+            group_status.status = GroupState::Incident;
+        }
+    }
+
+}
+
+impl Default for MemoryStorage {
+
+    fn default() -> Self {
+        MemoryStorage::new()
+    }
+
+}
+
+impl StorageBackend for MemoryStorage {
+
+    fn init_region(&mut self, region: &str, linked_groups: Vec<String>) {
+
+        self.region_storage.insert(region.to_string(), RegionStatus {
+            status: RegionState::Initial,
+            updated_at: Utc::now(),
+        });
+        self.region_metadata.insert(region.to_string(), RegionMetadata {
+            linked_groups
+        });
+    }
+
+    fn init_group(&mut self, region: &str, group: &str) {
+
+        let group_key = format!("{}.{}", region, group);
+
+        self.group_storage.insert(group_key, GroupStatus {
+            status: GroupState::Initial,
+            updated_at: Utc::now(),
+            last_metrics: vec![],
+            last_tests: vec![],
+            last_error: None,
+            last_error_detail: None
+        });
+    }
+
+    fn get_region_status(&self, region: &str) -> Option<&RegionStatus> {
+        self.region_storage.get(region)
+    }
+
+    fn get_group_status(&self, region: &str, group: &str) -> Option<&GroupStatus> {
+        
+        let group_key = format!("{}.{}", region, group);
+        self.group_storage.get(&group_key)
+    }
+
+    fn collect_test_metrics(&self) -> Vec<FullMetric> {
+
+        let mut metrics: Vec<FullMetric> = vec![];
+        for (group_name, group_status) in &self.group_storage {
+
+            for group_metric in &group_status.last_metrics {
+
+                let mut full_labels = group_metric.labels.clone();
+
+                let group_parts: Vec<&str> = group_name.split('.').collect();
+
+                if let Some(region_name) = group_parts.first() {
+                    full_labels.insert("region".into(), region_name.to_string());
+                }
+
+                if let Some(group_name) = group_parts.last() {
+                    full_labels.insert("group".into(), group_name.to_string());
+                }
+
+                metrics.push(FullMetric {
+                    name: group_metric.name.clone(),
+                    labels: full_labels,
+                    metric: group_metric.metric
+                });
+            }
+        }
+
+        metrics
+    }
+
+    fn collect_region_metrics(&self) -> Vec<FullMetric> {
+
+        let mut metrics: Vec<FullMetric> = vec![];
+        for (region_key, region_value) in &self.region_storage {
+
+            metrics.push(FullMetric {
+                name: "region".to_string(),
+                labels: HashMap::from([
+                    ("region_name".to_string(), region_key.clone())
+                ]),
+                metric: match region_value.status {
+                    RegionState::Up => 3f32,
+                    RegionState::Down => 0f32,
+                    RegionState::Initial => 1f32,
+                    RegionState::Warn => 2f32,
+                }
+            });
+        }
+
+        metrics
+    }
+
+    /// Keep group results in the history, ordered by timestamp: backfilled results are
+    /// older than the live results and are inserted at their original position
+    fn record_history(&mut self, record: GroupHistoryRecord) {
+
+        let position = self.group_history.partition_point(|existing| existing.timestamp <= record.timestamp);
+        self.group_history.insert(position, record);
+
+        while self.group_history.len() > MAX_HISTORY_RECORDS {
+            self.group_history.pop_front();
+        }
+    }
+
+    fn find_history(&self, region: Option<&str>) -> Vec<GroupHistoryItem> {
+
+        self.group_history.iter()
+            .filter(|record| region.map(|region| record.region == region).unwrap_or(true))
+            .map(|record| GroupHistoryItem {
+                region: record.region.clone(),
+                group: record.group.clone(),
+                timestamp: record.timestamp.to_rfc3339(),
+                working: record.working,
+                has_warnings: record.has_warnings,
+                backfilled: record.backfilled,
+                error_message: record.report.error.clone(),
+                error_detail: record.report.error_detail.clone(),
+                tests: record.report.tests.clone(),
+                metrics: record.report.metrics.clone()
+            })
+            .collect()
+    }
+
+    fn queue_probe(&mut self, region: &str, test: &str) -> u32 {
+
+        self.last_probe_id += 1;
+        self.probes.push_back(ProbeRecord {
+            id: self.last_probe_id,
+            region: region.to_string(),
+            test: test.to_string(),
+            status: ProbeStatus::Queued,
+            created_at: Utc::now(),
+            result: None
+        });
+
+        while self.probes.len() > MAX_PROBE_RECORDS {
+            self.probes.pop_front();
+        }
+
+        self.last_probe_id
+    }
+
+    /// Hand the queued probes of a region to its relay, probes that were not picked
+    /// up in time (relay down or disconnected) are expired instead
+    fn take_pending_probes(&mut self, region: &str) -> Vec<ProbeRequest> {
+
+        let expiry_limit = Utc::now() - Duration::seconds(PROBE_EXPIRY_SECONDS);
+
+        let mut pending_probes: Vec<ProbeRequest> = vec![];
+        for probe in self.probes.iter_mut().filter(|probe| probe.region == region && probe.status == ProbeStatus::Queued) {
+
+            if probe.created_at < expiry_limit {
+                probe.status = ProbeStatus::Expired;
+                continue;
+            }
+
+            probe.status = ProbeStatus::Running;
+            pending_probes.push(ProbeRequest {
+                id: probe.id,
+                test: probe.test.clone()
+            });
+        }
+
+        pending_probes
+    }
+
+    fn complete_probe(&mut self, region: &str, probe_id: u32, result: ProbeResult) -> Result<(), Error> {
+
+        let probe = self.probes.iter_mut()
+            .find(|probe| probe.id == probe_id && probe.region == region)
+            .ok_or_else(|| Error::basic(format!("Could not find probe {} in region {}", probe_id, region)))?;
+
+        probe.status = ProbeStatus::Done;
+        probe.result = Some(result);
+
+        Ok(())
+    }
+
+    fn get_probe(&self, probe_id: u32) -> Option<ProbeItem> {
+
+        self.probes.iter()
+            .find(|probe| probe.id == probe_id)
+            .map(|probe| ProbeItem {
+                id: probe.id,
+                region: probe.region.clone(),
+                test: probe.test.clone(),
+                status: probe.status,
+                created_at: probe.created_at.to_rfc3339(),
+                result: probe.result.clone()
+            })
+    }
+
+    fn find_incidents(&self) -> Vec<IncidentItem> {
+
+        let mut incidents: Vec<IncidentItem> = vec![];
+        for incident in &self.incidents {
+            incidents.push(incident.to_item())
+        }
+
+        incidents
+    }
+
+    fn get_incident(&self, incident_id: u32) -> Option<IncidentItem> {
+        
+        self.incidents.iter()
+            .find(|incident| incident.id == incident_id)
+            .map(|result| result.to_item())
+    }
+
+    fn compute_analytics(&self) -> RegionSummary {
+
+        let mut regions: Vec<RegionSummaryItem> = vec![];
+        for (region_key, region_value) in &self.region_storage {
+
+            regions.push(RegionSummaryItem {
+                name: region_key.to_string(),
+                status: match region_value.status {
+                    RegionState::Up => "up".to_string(),
+                    RegionState::Down => "down".to_string(),
+                    RegionState::Initial => "initial".to_string(),
+                    RegionState::Warn => "warn".to_string()
+                },
+                last_update: region_value.updated_at.to_rfc3339()
+            });
+        }
+
+        let mut groups: Vec<GroupSummaryItem> = vec![];
+        for (group_key, group_value) in &self.group_storage {
+
+            groups.push(GroupSummaryItem {
+                name: group_key.to_string(),
+                status: match group_value.status {
+                    GroupState::Up => "up".to_string(),
+                    GroupState::Warn => "warn".to_string(),
+                    GroupState::Down => "down".to_string(),
+                    GroupState::Incident => "incident".to_string(),
+                    GroupState::Initial => "initial".to_string()
+                },
+                last_update: group_value.updated_at.to_rfc3339(),
+                tests: group_value.last_tests.clone()
+            });
+        }
+
+        let incidents = self.find_incidents();
+
+        RegionSummary {
+            regions,
+            groups,
+            incidents
+        }
+    }
+
+    fn refresh_region(&mut self, region: &str, has_warnings: bool) {
+
+        // TODO Should also track unstable states in regions
+
+        self.region_storage.insert(region.to_string(), RegionStatus {
+            status: match has_warnings {
+                true => RegionState::Warn,
+                false => RegionState::Up
+            },
+            updated_at: Utc::now()
+        });
+    }
+
+    fn trigger_region_incident(&mut self, region: &str, ms_threshold: i64) -> Result<(), Error> {
+
+        let old_status = self.region_storage.get(region).ok_or_else(|| Error::basic(format!("Could not find region storage {}", region)))?;
+
+        // The 'chrono UTC' type implements the 'Copy' trait and does not
+        // require a clone() call, which simplifies ownership. 
+        let updated_at = old_status.updated_at;
+        
+        self.region_storage.insert(region.to_string(), RegionStatus {
+            status: RegionState::Down,
+            updated_at
+        });
+
+        let region_metadata = self.region_metadata.get(region).ok_or_else(|| Error::basic(format!("Could not find region metadata {}", region)))?;
+        for impacted_group in &region_metadata.linked_groups {
+
+            self.group_storage.insert(format!("{}.{}", region, impacted_group), GroupStatus {
+                status: GroupState::Incident,
+                updated_at: Utc::now(),
+                last_metrics: vec![],
+                last_tests: vec![],
+                last_error: None,
+                last_error_detail: None
+            });
+        }
+
+        self.incidents.push(IncidentRecord {
+            id: self.last_incident_id,
+            region: region.to_string(),
+            group: None,
+            message: format!("Region {} is DOWN", region),
+            timestamp: Utc::now(),
+            resolved_at: None,
+            error_message: Some(format!("Region relay has not sent heartbeat in time ({}ms threshold exceeded)", ms_threshold)),
+            error_details: None,
+            tests: vec![]
+        });
+        self.last_incident_id += 1;
+
+        Ok(())
+    }
+
+    fn refresh_group(&mut self, region: &str, group: &str, status: GroupState, report: GroupReport) -> Result<(), Error> {
+
+        let group_key = format!("{}.{}", region, group);
+        let updated_at = match status {
+            GroupState::Down => {
+                // A group marked as 'down' will not be updated, allowing to trigger an incident
+                // after X milliseconds without update on the DOWN group
+                let old_status = self.group_storage.get(&group_key).ok_or_else(|| Error::basic(format!("Could not find group storage {}", group_key)))?;
+                old_status.updated_at
+            },
+            _ => Utc::now()
+        };
+
+        self.group_storage.insert(group_key, GroupStatus {
+            status,
+            updated_at,
+            last_metrics: report.metrics,
+            last_tests: report.tests,
+            last_error: report.error,
+            last_error_detail: report.error_detail
+        });
+
+        Ok(())
+    }
+
+    fn trigger_group_incident(&mut self, region: &str, group: &str) -> Result<(), Error> {
+
+        let group_key = format!("{}.{}", region, group);
+        let old_status = self.group_storage.get(&group_key).ok_or_else(|| Error::basic(format!("Could not find group storage {}", group_key)))?;
+
+        // The 'chrono UTC' type implements the 'Copy' trait and does not
+        // require a clone() call, which simplifies ownership. 
+        let updated_at = old_status.updated_at;
+
+        let last_error = old_status.clone().last_error;
+        let last_error_detail = old_status.last_error_detail.clone();
+        let last_tests = old_status.last_tests.clone();
+        
+        // Move to incident, this will avoid re-trigger alerts
+        self.group_storage.insert(group_key, GroupStatus {
+            status: GroupState::Incident,
+            updated_at,
+            last_metrics: old_status.last_metrics.clone(),
+            last_tests: last_tests.clone(),
+            last_error: last_error.clone(),
+            last_error_detail: last_error_detail.clone()
+        });
+
+        let error_message = format!("Triggered from group relay ({})", last_error.unwrap_or("-".into()));
+        self.incidents.push(IncidentRecord {
+            id: self.last_incident_id,
+            region: region.to_string(),
+            group: Some(group.to_string()),
+            message: format!("Group {}.{} is DOWN", region, group),
+            timestamp: Utc::now(),
+            resolved_at: None,
+            error_message: Some(error_message),
+            error_details: last_error_detail,
+            tests: last_tests
+        });
+        self.last_incident_id += 1;
+
+        Ok(())
+    }
+
+    fn resolve_region_incidents(&mut self, region: &str) -> Vec<IncidentItem> {
+        self.resolve_incidents(region, None)
+    }
+
+    fn resolve_group_incidents(&mut self, region: &str, group: &str) -> Vec<IncidentItem> {
+        self.resolve_incidents(region, Some(group))
+    }
+
+}
+
+#[cfg(test)]
+mod tests {
+
+    use super::*;
+
+    fn build_record(timestamp: &str, backfilled: bool) -> GroupHistoryRecord {
+
+        GroupHistoryRecord {
+            region: "region-north".to_string(),
+            group: "default".to_string(),
+            timestamp: DateTime::parse_from_rfc3339(timestamp).unwrap().with_timezone(&Utc),
+            working: !backfilled,
+            has_warnings: false,
+            backfilled,
+            report: GroupReport {
+                metrics: vec![],
+                tests: vec![],
+                error: None,
+                error_detail: None
+            }
+        }
+    }
+
+    #[test]
+    fn should_hand_probes_to_region_relay() {
+
+        let mut storage = MemoryStorage::new();
+
+        let probe_id = storage.queue_probe("region-south", "ping 10.1.2.3");
+        storage.queue_probe("region-north", "ping 10.5.0.1");
+
+        assert!(storage.take_pending_probes("region-east").is_empty());
+
+        let pending_probes = storage.take_pending_probes("region-south");
+        assert_eq!(pending_probes.len(), 1);
+        assert_eq!(pending_probes[0].test, "ping 10.1.2.3");
+        assert_eq!(storage.get_probe(probe_id).unwrap().status, ProbeStatus::Running);
+
+        // A probe is only handed once to the relay
+        assert!(storage.take_pending_probes("region-south").is_empty());
+
+        let probe_result = ProbeResult {
+            target: "10.1.2.3".to_string(),
+            result: ResultCategory::Fail,
+            duration_ms: 2000,
+            reason: Some(FailureReason::PacketLoss { loss_pct: 100.0 }),
+            error: None,
+            metrics: HashMap::new()
+        };
+        assert!(storage.complete_probe("region-north", probe_id, probe_result.clone()).is_err());
+        storage.complete_probe("region-south", probe_id, probe_result).unwrap();
+
+        let probe = storage.get_probe(probe_id).unwrap();
+        assert_eq!(probe.status, ProbeStatus::Done);
+        assert_eq!(probe.result.unwrap().reason, Some(FailureReason::PacketLoss { loss_pct: 100.0 }));
+    }
+
+    #[test]
+    fn should_resolve_incidents() {
+
+        let mut storage = MemoryStorage::new();
+
+        storage.init_region("region-north", vec!["default".to_string()]);
+        storage.init_group("region-north", "default");
+
+        storage.trigger_group_incident("region-north", "default").unwrap();
+        storage.trigger_region_incident("region-north", 10000).unwrap();
+        assert!(storage.find_incidents().iter().all(|incident| incident.status == IncidentStatus::Open));
+
+        // Region and group incidents are resolved separately
+        let resolved_incidents = storage.resolve_region_incidents("region-north");
+        assert_eq!(resolved_incidents.len(), 1);
+        assert_eq!(resolved_incidents[0].id, 1);
+        assert!(resolved_incidents[0].resolved_at.is_some());
+        assert_eq!(storage.get_incident(0).unwrap().status, IncidentStatus::Open);
+
+        assert_eq!(storage.resolve_group_incidents("region-north", "default").len(), 1);
+        assert!(storage.resolve_group_incidents("region-north", "default").is_empty());
+        assert_eq!(storage.get_incident(0).unwrap().status, IncidentStatus::Resolved);
+    }
+
+    #[test]
+    fn should_order_backfilled_history() {
+
+        let mut storage = MemoryStorage::new();
+
+        storage.init_region("region-north", vec!["default".to_string()]);
+        storage.init_group("region-north", "default");
+
+        storage.record_history(build_record("2023-05-01T10:10:00Z", false));
+        storage.record_history(build_record("2023-05-01T10:00:00Z", true));
+        storage.record_history(build_record("2023-05-01T10:05:00Z", true));
+
+        let history = storage.find_history(Some("region-north"));
+        let timestamps: Vec<&str> = history.iter().map(|item| item.timestamp.as_str()).collect();
+
+        assert_eq!(timestamps, vec!["2023-05-01T10:00:00+00:00", "2023-05-01T10:05:00+00:00", "2023-05-01T10:10:00+00:00"]);
+        assert!(history[0].backfilled);
+        assert!(storage.find_history(Some("region-south")).is_empty());
+
+        // Backfilled results do not refresh the group state
+        assert!(matches!(storage.get_group_status("region-north", "default").unwrap().status, GroupState::Initial));
+    }
+
+}
@@ -0,0 +1,304 @@
+use std::collections::HashMap;
+use std::sync::Arc;
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::RwLock;
+use chrono::{DateTime, Utc};
+
+use crate::common::error::Error;
+use crate::relay::model::{FailureReason, ProbeRequest, ResultCategory};
+
+pub mod memory;
+pub mod file;
+
+pub type Storage = Arc<RwLock<dyn StorageBackend + Send + Sync + 'static>>;
+
+#[derive(Clone)]
+pub enum RegionState {
+    Initial,
+    Up,
+    Warn,
+    Down
+}
+
+#[derive(Clone)]
+pub enum GroupState {
+    Initial,
+    Up,
+    Warn,
+    Down,
+    Incident
+}
+
+#[derive(Clone)]
+pub struct RegionStatus {
+    pub status: RegionState,
+    pub updated_at: DateTime<Utc>,
+}
+
+pub struct FullMetric {
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    pub metric: f32
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct GroupMetrics {
+    pub name: String,
+    pub labels: HashMap<String, String>,
+    pub metric: f32
+}
+
+#[derive(Clone)]
+pub struct GroupStatus {
+    pub status: GroupState,
+    pub updated_at: DateTime<Utc>,
+    pub last_metrics: Vec<GroupMetrics>,
+    pub last_tests: Vec<TestOutcome>,
+    pub last_error: Option<String>,
+    pub last_error_detail: Option<String>
+}
+
+/// Latest results reported by a relay for a group
+#[derive(Deserialize,Serialize,Clone)]
+pub struct GroupReport {
+    pub metrics: Vec<GroupMetrics>,
+    pub tests: Vec<TestOutcome>,
+    pub error: Option<String>,
+    pub error_detail: Option<String>
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct TestOutcome {
+    pub test: String,
+    pub target: String,
+    pub result: ResultCategory,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub reason: Option<FailureReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub error: Option<String>
+}
+
+/// Incident on a region (no group) or on a group, the incident stays open
+/// until the region or the group is healthy again
+#[derive(Deserialize,Serialize,Clone)]
+pub struct IncidentRecord {
+    pub id: u32,
+    #[serde(default)]
+    pub region: String,
+    #[serde(default)]
+    pub group: Option<String>,
+    pub message: String,
+    pub timestamp: DateTime<Utc>,
+    #[serde(default)]
+    pub resolved_at: Option<DateTime<Utc>>,
+    pub error_message: Option<String>,
+    pub error_details: Option<String>,
+    pub tests: Vec<TestOutcome>
+}
+
+impl IncidentRecord {
+
+    /// Duration of the incident, up to now for open incidents
+    pub fn duration(&self) -> chrono::Duration {
+
+        self.resolved_at.unwrap_or_else(Utc::now) - self.timestamp
+    }
+
+    pub fn to_item(&self) -> IncidentItem {
+
+        IncidentItem {
+            id: self.id,
+            message: self.message.clone(),
+            timestamp: self.timestamp.to_rfc3339(),
+            status: match self.resolved_at {
+                Some(_) => IncidentStatus::Resolved,
+                None => IncidentStatus::Open
+            },
+            resolved_at: self.resolved_at.map(|resolved_at| resolved_at.to_rfc3339()),
+            duration_secs: self.duration().num_seconds(),
+            error_message: self.error_message.clone(),
+            error_details: self.error_details.clone(),
+            tests: self.tests.clone()
+        }
+    }
+
+}
+
+/// Group results received from a relay, either live or replayed after an
+/// outage between the relay and the server ('backfilled' results)
+#[derive(Deserialize,Serialize,Clone)]
+pub struct GroupHistoryRecord {
+    pub region: String,
+    pub group: String,
+    pub timestamp: DateTime<Utc>,
+    pub working: bool,
+    pub has_warnings: bool,
+    pub backfilled: bool,
+    pub report: GroupReport
+}
+
+#[derive(Deserialize,Serialize,Clone,Copy,PartialEq,Debug)]
+#[serde(rename_all = "lowercase")]
+pub enum ProbeStatus {
+    Queued,
+    Running,
+    Done,
+    Expired
+}
+
+#[derive(Deserialize,Serialize,Clone)]
+pub struct ProbeResult {
+    pub target: String,
+    pub result: ResultCategory,
+    pub duration_ms: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub reason: Option<FailureReason>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[serde(default)]
+    pub error: Option<String>,
+    #[serde(default)]
+    pub metrics: HashMap<String, f32>
+}
+
+/// On-demand test requested from the CLI for a region, the test is handed to
+/// the region relay on its next update and the relay sends the result back
+pub struct ProbeRecord {
+    pub id: u32,
+    pub region: String,
+    pub test: String,
+    pub status: ProbeStatus,
+    pub created_at: DateTime<Utc>,
+    pub result: Option<ProbeResult>
+}
+
+#[derive(Deserialize,Serialize)]
+pub struct ProbeItem {
+    pub id: u32,
+    pub region: String,
+    pub test: String,
+    pub status: ProbeStatus,
+    pub created_at: String,
+    #[serde(default)]
+    pub result: Option<ProbeResult>
+}
+
+#[derive(Deserialize,Serialize)]
+pub struct RegionSummary {
+    pub regions: Vec<RegionSummaryItem>,
+    pub groups: Vec<GroupSummaryItem>,
+    pub incidents: Vec<IncidentItem>
+}
+
+#[derive(Deserialize,Serialize)]
+pub struct RegionSummaryItem {
+    pub name: String,
+    pub status: String,
+    pub last_update: String
+}
+
+#[derive(Deserialize,Serialize)]
+pub struct GroupSummaryItem {
+    pub name: String,
+    pub status: String,
+    pub last_update: String,
+    #[serde(default)]
+    pub tests: Vec<TestOutcome>
+}
+
+#[derive(Deserialize,Serialize)]
+pub struct GroupHistoryItem {
+    pub region: String,
+    pub group: String,
+    pub timestamp: String,
+    pub working: bool,
+    pub has_warnings: bool,
+    pub backfilled: bool,
+    pub error_message: Option<String>,
+    pub error_detail: Option<String>,
+    pub tests: Vec<TestOutcome>,
+    pub metrics: Vec<GroupMetrics>
+}
+
+#[derive(Deserialize,Serialize,Clone,Copy,PartialEq,Debug,Default)]
+#[serde(rename_all = "lowercase")]
+pub enum IncidentStatus {
+    #[default]
+    Open,
+    Resolved
+}
+
+#[derive(Deserialize,Serialize)]
+pub struct IncidentItem {
+    pub id: u32,
+    pub message: String,
+    pub timestamp: String,
+    #[serde(default)]
+    pub status: IncidentStatus,
+    #[serde(default)]
+    pub resolved_at: Option<String>,
+    #[serde(default)]
+    pub duration_secs: i64,
+    pub error_message: Option<String>,
+    pub error_details: Option<String>,
+    #[serde(default)]
+    pub tests: Vec<TestOutcome>
+}
+
+/**
+ * State of the server: live status of the regions and groups, incidents, group history
+ * and on-demand probes. The in-memory backend loses everything on restart, while the
+ * file backend keeps the incidents and the group history between restarts.
+ */
+pub trait StorageBackend {
+
+    fn init_region(&mut self, region: &str, linked_groups: Vec<String>);
+
+    fn init_group(&mut self, region: &str, group: &str);
+
+    fn get_region_status(&self, region: &str) -> Option<&RegionStatus>;
+
+    fn get_group_status(&self, region: &str, group: &str) -> Option<&GroupStatus>;
+
+    fn collect_test_metrics(&self) -> Vec<FullMetric>;
+
+    fn collect_region_metrics(&self) -> Vec<FullMetric>;
+
+    fn record_history(&mut self, record: GroupHistoryRecord);
+
+    fn find_history(&self, region: Option<&str>) -> Vec<GroupHistoryItem>;
+
+    fn queue_probe(&mut self, region: &str, test: &str) -> u32;
+
+    fn take_pending_probes(&mut self, region: &str) -> Vec<ProbeRequest>;
+
+    fn complete_probe(&mut self, region: &str, probe_id: u32, result: ProbeResult) -> Result<(), Error>;
+
+    fn get_probe(&self, probe_id: u32) -> Option<ProbeItem>;
+
+    fn find_incidents(&self) -> Vec<IncidentItem>;
+
+    fn get_incident(&self, incident_id: u32) -> Option<IncidentItem>;
+
+    fn compute_analytics(&self) -> RegionSummary;
+
+    fn refresh_region(&mut self, region: &str, has_warnings: bool);
+
+    fn trigger_region_incident(&mut self, region: &str, ms_threshold: i64) -> Result<(), Error>;
+
+    fn refresh_group(&mut self, region: &str, group: &str, status: GroupState, report: GroupReport) -> Result<(), Error>;
+
+    fn trigger_group_incident(&mut self, region: &str, group: &str) -> Result<(), Error>;
+
+    /// Resolve the open incidents of a region (group incidents excluded) and
+    /// return the resolved incidents
+    fn resolve_region_incidents(&mut self, region: &str) -> Vec<IncidentItem>;
+
+    /// Resolve the open incidents of a group and return the resolved incidents
+    fn resolve_group_incidents(&mut self, region: &str, group: &str) -> Vec<IncidentItem>;
+
+}
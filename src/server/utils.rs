@@ -18,7 +18,7 @@ pub struct ServerErr {
 impl ServerErr {
 
     /// Build a HTTP '400 Bad Request' error
-    pub fn _bad_request<M>(message: M) -> ServerErr where M: Into<String> {
+    pub fn bad_request<M>(message: M) -> ServerErr where M: Into<String> {
 
         ServerErr { 
             status: 400,